@@ -0,0 +1,203 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::renderer::{FrameRange, Renderer};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// Renders all frames to an animated png
+///
+/// Every frame is compressed by the png encoder, its image data is then moved
+/// into the animation chunks. `loops` is the number of times the animation
+/// repeats after playing once, 0 repeats forever.
+pub fn write_apng<R: Renderer>(
+    renderer: &mut R,
+    frames: FrameRange,
+    path: &Path,
+    loops: u32,
+) -> io::Result<()> {
+    let mut header = Vec::new();
+    let mut images = Vec::new();
+    let mut size = (0, 0);
+    for frame in 0..frames.count() {
+        let (pixels, width, height) = renderer.render(frames.time(frame));
+        let png = encode(&pixels, width, height)?;
+        let mut data = Vec::new();
+        for (kind, chunk) in chunks(&png) {
+            match kind {
+                b"IHDR" if frame == 0 => header = chunk.to_vec(),
+                b"IDAT" => data.push(chunk.to_vec()),
+                _ => {}
+            }
+        }
+        size = (width as u32, height as u32);
+        images.push(data);
+        info!("Frame {} of {} done", frame, frames.steps);
+    }
+
+    let (delay_num, delay_den) = if frames.fps.fract() == 0. {
+        (1, frames.fps as u16)
+    } else {
+        ((1000. / frames.fps).round() as u16, 1000)
+    };
+
+    let mut w = BufWriter::new(File::create(path)?);
+    w.write_all(&SIGNATURE)?;
+    write_chunk(&mut w, b"IHDR", &header)?;
+
+    // acTL counts the first play too, and can't go beyond 2³¹ - 1
+    let plays = if loops == 0 { 0 } else { loops.min(i32::MAX as u32 - 1) + 1 };
+    let mut actl = Vec::new();
+    actl.extend_from_slice(&(images.len() as u32).to_be_bytes());
+    actl.extend_from_slice(&plays.to_be_bytes());
+    write_chunk(&mut w, b"acTL", &actl)?;
+
+    // fcTL and fdAT chunks share one sequence counter
+    let mut sequence: u32 = 0;
+    for (frame, data) in images.iter().enumerate() {
+        let mut fctl = Vec::new();
+        fctl.extend_from_slice(&sequence.to_be_bytes());
+        fctl.extend_from_slice(&size.0.to_be_bytes());
+        fctl.extend_from_slice(&size.1.to_be_bytes());
+        fctl.extend_from_slice(&0u32.to_be_bytes()); // x offset
+        fctl.extend_from_slice(&0u32.to_be_bytes()); // y offset
+        fctl.extend_from_slice(&delay_num.to_be_bytes());
+        fctl.extend_from_slice(&delay_den.to_be_bytes());
+        fctl.extend_from_slice(&[0, 0]); // No disposal, replace the previous frame
+        write_chunk(&mut w, b"fcTL", &fctl)?;
+        sequence += 1;
+
+        for chunk in data {
+            if frame == 0 {
+                // The first frame is also the default image for viewers without apng support
+                write_chunk(&mut w, b"IDAT", chunk)?;
+            } else {
+                let mut fdat = sequence.to_be_bytes().to_vec();
+                fdat.extend_from_slice(chunk);
+                write_chunk(&mut w, b"fdAT", &fdat)?;
+                sequence += 1;
+            }
+        }
+    }
+
+    write_chunk(&mut w, b"IEND", &[])?;
+    w.flush()
+}
+
+fn encode(pixels: &[u8], width: u16, height: u16) -> io::Result<Vec<u8>> {
+    let mut png = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png, width.into(), height.into());
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(pixels)?;
+    }
+    Ok(png)
+}
+
+/// Splits an encoded png into its chunk types and data
+fn chunks(png: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut chunks = Vec::new();
+    let mut i = SIGNATURE.len();
+    while i + 12 <= png.len() {
+        let length = u32::from_be_bytes([png[i], png[i + 1], png[i + 2], png[i + 3]]) as usize;
+        chunks.push((&png[i + 4..i + 8], &png[i + 8..i + 8 + length]));
+        i += 12 + length;
+    }
+    chunks
+}
+
+fn write_chunk<W: Write>(w: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    let crc = crc32(kind.iter().chain(data.iter()));
+    w.write_all(&crc.to_be_bytes())
+}
+
+fn crc32<'a, I: Iterator<Item = &'a u8>>(bytes: I) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Grey frames getting brighter
+    struct Grey;
+
+    impl Renderer for Grey {
+        fn render(&mut self, t: f64) -> (Vec<u8>, u16, u16) {
+            (vec![(50. * t) as u8; 4 * 4 * 3], 4, 3)
+        }
+    }
+
+    fn write(loops: u32) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!("apng-{}-{}.png", std::process::id(), loops));
+        write_apng(&mut Grey, FrameRange::new(0., 2., 1.), &path, loops).unwrap();
+        let png = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        png
+    }
+
+    fn number(bytes: &[u8]) -> u32 {
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    #[test]
+    fn crc32_known_values() {
+        assert_eq!(crc32(b"123456789".iter()), 0xCBF4_3926);
+        assert_eq!(crc32(b"IEND".iter()), 0xAE42_6082);
+    }
+
+    #[test]
+    fn chunks_in_order() {
+        let png = write(2);
+        assert_eq!(png[..8], SIGNATURE);
+        let kinds: Vec<&[u8]> = chunks(&png).iter().map(|&(kind, _)| kind).collect();
+        let expected: [&[u8]; 9] = [
+            b"IHDR", b"acTL", b"fcTL", b"IDAT", b"fcTL", b"fdAT", b"fcTL", b"fdAT", b"IEND",
+        ];
+        assert_eq!(kinds, expected);
+
+        // Checksums cover the type and the data
+        let mut i = 8;
+        while i < png.len() {
+            let length = number(&png[i..]) as usize;
+            assert_eq!(number(&png[i + 8 + length..]), crc32(png[i + 4..i + 8 + length].iter()));
+            i += 12 + length;
+        }
+    }
+
+    #[test]
+    fn sequence_numbers_count_up() {
+        let png = write(0);
+        let sequence: Vec<u32> = chunks(&png)
+            .iter()
+            .filter(|&&(kind, _)| kind == b"fcTL" || kind == b"fdAT")
+            .map(|&(_, data)| number(data))
+            .collect();
+        assert_eq!(sequence, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn plays_include_the_first() {
+        let actl = |png: &[u8]| {
+            let (_, data) = chunks(png).into_iter().find(|&(kind, _)| kind == b"acTL").unwrap();
+            (number(data), number(&data[4..]))
+        };
+        assert_eq!(actl(&write(0)), (3, 0));
+        assert_eq!(actl(&write(2)), (3, 3));
+        assert_eq!(actl(&write(u32::MAX)), (3, i32::MAX as u32));
+    }
+}
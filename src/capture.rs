@@ -0,0 +1,50 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::png_renderer::save_image;
+
+/// Saves what the viewer shows as png, single screenshots or recorded sequences
+pub struct Capture {
+    /// Directory and number of the next frame while recording
+    recording: Option<(PathBuf, u64)>,
+}
+
+impl Capture {
+    pub fn new() -> Capture {
+        Capture { recording: None }
+    }
+
+    pub fn screenshot(&self, bytes: &[u8], width: u16, height: u16) {
+        fs::create_dir_all("screenshots").unwrap();
+        let path = next_free(|i| PathBuf::from(format!("screenshots/screenshot-{:0>5}.png", i)));
+        save_image(&path, bytes, width, height, png::BitDepth::Eight);
+        info!("Saved screenshot {:?}", path);
+    }
+
+    /// Starts recording into a new directory, or stops the current recording
+    pub fn toggle_recording(&mut self) {
+        match self.recording.take() {
+            Some((directory, frames)) => info!("Recorded {} frames to {:?}", frames, directory),
+            None => {
+                let directory = next_free(|i| PathBuf::from(format!("recordings/take-{:0>3}", i)));
+                fs::create_dir_all(&directory).unwrap();
+                info!("Recording to {:?}", directory);
+                self.recording = Some((directory, 0));
+            }
+        }
+    }
+
+    /// Saves the frame if recording
+    pub fn record(&mut self, bytes: &[u8], width: u16, height: u16) {
+        if let Some((directory, frame)) = &mut self.recording {
+            let path = directory.join(format!("frame-{:0>5}.png", frame));
+            save_image(&path, bytes, width, height, png::BitDepth::Eight);
+            *frame += 1;
+        }
+    }
+}
+
+/// The first numbered path that doesn't exist yet
+fn next_free<F: Fn(u64) -> PathBuf>(path: F) -> PathBuf {
+    (0..).map(path).find(|p| !p.exists()).unwrap()
+}
@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+
+use miniquad::{KeyCode, MouseButton};
+
+use crate::raytracer::{Camera, Vec3};
+
+/// Radians per pixel of mouse movement
+const LOOK_SPEED: f64 = 0.005;
+/// Units per second in fly mode
+const FLY_SPEED: f64 = 100.;
+/// Keeps the camera from looking straight up or down
+const MAX_PITCH: f64 = 89. * std::f64::consts::PI / 180.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// The camera follows the animation
+    Scripted,
+    /// Left drag orbits around a target, right drag pans, the wheel dollies
+    Orbit,
+    /// WASD moves, Q and E move down and up, left drag looks around
+    Fly,
+}
+
+/// Steers a camera with keyboard and mouse
+pub struct Controls {
+    pub mode: Mode,
+    /// The point the orbit camera circles around
+    target: Vec3,
+    held: HashSet<KeyCode>,
+    dragging: Option<MouseButton>,
+    mouse: (f32, f32),
+}
+
+impl Controls {
+    pub fn new() -> Controls {
+        Controls {
+            mode: Mode::Scripted,
+            target: Vec3::NULL,
+            held: HashSet::new(),
+            dragging: None,
+            mouse: (0., 0.),
+        }
+    }
+
+    pub fn key_down(&mut self, camera: &mut Camera, keycode: KeyCode) {
+        match keycode {
+            KeyCode::C => {
+                self.mode = match self.mode {
+                    Mode::Scripted => Mode::Orbit,
+                    Mode::Orbit => Mode::Fly,
+                    Mode::Fly => Mode::Scripted,
+                };
+                if self.mode == Mode::Orbit {
+                    // Orbit around what the camera looks at, at the distance of the origin
+                    self.target = camera.pos + camera.dir * camera.pos.mag();
+                }
+                camera.scripted = self.mode == Mode::Scripted;
+                info!("Camera mode: {:?}", self.mode);
+            }
+            KeyCode::P => {
+                println!(
+                    "camera: Camera {{ roll: {:?}, ..Camera::look_at({:?}, {:?}, {:?}, Fov::{:?}) }},",
+                    camera.roll,
+                    camera.pos,
+                    camera.pos + camera.dir,
+                    camera.up,
+                    camera.fov
+                );
+            }
+            _ => {
+                self.held.insert(keycode);
+            }
+        }
+    }
+
+    pub fn key_up(&mut self, keycode: KeyCode) {
+        self.held.remove(&keycode);
+    }
+
+    pub fn mouse_down(&mut self, button: MouseButton, x: f32, y: f32) {
+        self.dragging = Some(button);
+        self.mouse = (x, y);
+    }
+
+    pub fn mouse_up(&mut self) {
+        self.dragging = None;
+    }
+
+    pub fn mouse_motion(&mut self, camera: &mut Camera, x: f32, y: f32) {
+        let dx = (x - self.mouse.0) as f64;
+        let dy = (y - self.mouse.1) as f64;
+        self.mouse = (x, y);
+
+        match (self.mode, self.dragging) {
+            (Mode::Orbit, Some(MouseButton::Left)) => {
+                let offset = look(-(camera.pos - self.target), -dx * LOOK_SPEED, -dy * LOOK_SPEED);
+                camera.pos = self.target - offset;
+                camera.dir = offset.norm();
+            }
+            (Mode::Orbit, Some(_)) => {
+                let right = camera.dir.cross(Vec3::UP).norm();
+                let up = right.cross(camera.dir);
+                // Move the scene with the mouse, faster when further away
+                let scale = (camera.pos - self.target).mag() * 0.002;
+                let delta = (right * -dx + up * dy) * scale;
+                camera.pos = camera.pos + delta;
+                self.target = self.target + delta;
+            }
+            (Mode::Fly, Some(MouseButton::Left)) => {
+                camera.dir = look(camera.dir, -dx * LOOK_SPEED, -dy * LOOK_SPEED).norm();
+            }
+            _ => {}
+        }
+    }
+
+    pub fn mouse_wheel(&mut self, camera: &mut Camera, dy: f32) {
+        match self.mode {
+            Mode::Orbit => {
+                let distance = (camera.pos - self.target).mag() * 0.9f64.powf(dy.signum() as f64);
+                camera.pos = self.target - camera.dir * distance.max(1.);
+            }
+            Mode::Fly => camera.pos = camera.pos + camera.dir * (dy.signum() as f64 * FLY_SPEED * 0.1),
+            Mode::Scripted => {}
+        }
+    }
+
+    /// Moves the fly camera by the held keys, `dt` is in seconds
+    pub fn update(&mut self, camera: &mut Camera, dt: f64) {
+        if self.mode != Mode::Fly {
+            return;
+        }
+        let right = camera.dir.cross(Vec3::UP).norm();
+        let speed = if self.held.contains(&KeyCode::LeftShift) { FLY_SPEED * 4. } else { FLY_SPEED };
+        let mut velocity = Vec3::NULL;
+        for key in &self.held {
+            velocity = velocity
+                + match key {
+                    KeyCode::W => camera.dir,
+                    KeyCode::S => -camera.dir,
+                    KeyCode::D => right,
+                    KeyCode::A => -right,
+                    KeyCode::E => Vec3::UP,
+                    KeyCode::Q => -Vec3::UP,
+                    _ => Vec3::NULL,
+                };
+        }
+        camera.pos = camera.pos + velocity * (speed * dt);
+    }
+}
+
+/// Turns a direction by yaw around the up axis and by pitch towards it
+fn look(dir: Vec3, yaw: f64, pitch: f64) -> Vec3 {
+    let dir = dir.rotate(Vec3::UP, yaw);
+    let right = dir.cross(Vec3::UP).norm();
+    let current = dir.norm().dot(Vec3::UP).asin();
+    let pitch = (current + pitch).clamp(-MAX_PITCH, MAX_PITCH) - current;
+    dir.rotate(right, pitch)
+}
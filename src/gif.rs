@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::renderer::{FrameRange, Renderer};
+
+/// Maximum number of pixels per frame used to pick the palette
+const PALETTE_SAMPLES: usize = 20000;
+
+/// Renders all frames to an animated gif
+///
+/// Every frame gets its own 256 colour palette. `loops` is the number of
+/// times the animation repeats after playing once, 0 repeats forever.
+pub fn write_gif<R: Renderer>(
+    renderer: &mut R,
+    frames: FrameRange,
+    path: &Path,
+    dither: bool,
+    loops: u32,
+) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    let mut header_written = false;
+
+    for frame in 0..frames.count() {
+        let (pixels, width, height) = renderer.render(frames.time(frame));
+        if !header_written {
+            w.write_all(b"GIF89a")?;
+            w.write_all(&width.to_le_bytes())?;
+            w.write_all(&height.to_le_bytes())?;
+            // No global colour table, background colour 0, square pixels
+            w.write_all(&[0x00, 0x00, 0x00])?;
+            // Netscape extension for looping
+            w.write_all(&[0x21, 0xFF, 0x0B])?;
+            w.write_all(b"NETSCAPE2.0")?;
+            w.write_all(&[0x03, 0x01])?;
+            w.write_all(&(loops.min(u16::MAX as u32) as u16).to_le_bytes())?;
+            w.write_all(&[0x00])?;
+            header_written = true;
+        }
+
+        // Delays are in hundredths of a second, so spread the rounding error over the frames
+        let delay = ((frame + 1) as f64 * 100. / frames.fps).round()
+            - (frame as f64 * 100. / frames.fps).round();
+        // Graphic control extension, frames replace each other
+        w.write_all(&[0x21, 0xF9, 0x04, 0x04])?;
+        w.write_all(&(delay as u16).to_le_bytes())?;
+        w.write_all(&[0x00, 0x00])?;
+
+        let palette = palette(&pixels);
+        let indices = quantize(&pixels, width as usize, height as usize, &palette, dither);
+
+        // Image descriptor with a local colour table of 256 entries
+        w.write_all(&[0x2C, 0x00, 0x00, 0x00, 0x00])?;
+        w.write_all(&width.to_le_bytes())?;
+        w.write_all(&height.to_le_bytes())?;
+        w.write_all(&[0x87])?;
+        for i in 0..256 {
+            w.write_all(palette.get(i).unwrap_or(&[0, 0, 0]))?;
+        }
+
+        w.write_all(&[8])?;
+        for block in lzw(&indices, 8).chunks(255) {
+            w.write_all(&[block.len() as u8])?;
+            w.write_all(block)?;
+        }
+        w.write_all(&[0x00])?;
+        info!("Frame {} of {} done", frame, frames.steps);
+    }
+
+    w.write_all(&[0x3B])?;
+    w.flush()
+}
+
+/// Picks up to 256 colours by median cut
+fn palette(pixels: &[u8]) -> Vec<[u8; 3]> {
+    let step = (pixels.len() / 4 / PALETTE_SAMPLES).max(1);
+    let mut colors: Vec<[u8; 3]> = pixels
+        .chunks(4)
+        .step_by(step)
+        .map(|p| [p[0], p[1], p[2]])
+        .collect();
+
+    let mut boxes = vec![(0, colors.len())];
+    while boxes.len() < 256 {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .map(|(i, &(start, end))| {
+                let (channel, range) = widest_channel(&colors[start..end]);
+                (i, channel, range)
+            })
+            .max_by_key(|&(_, _, range)| range);
+        let (i, channel, range) = match widest {
+            Some(x) => x,
+            None => break,
+        };
+        if range == 0 {
+            break; // Every box has a single colour
+        }
+        let (start, end) = boxes[i];
+        colors[start..end].sort_unstable_by_key(|c| c[channel]);
+        let median = (start + end) / 2;
+        boxes[i] = (start, median);
+        boxes.push((median, end));
+    }
+
+    boxes
+        .iter()
+        .map(|&(start, end)| {
+            let mut sum = [0usize; 3];
+            for color in &colors[start..end] {
+                for c in 0..3 {
+                    sum[c] += color[c] as usize;
+                }
+            }
+            let n = (end - start).max(1);
+            [(sum[0] / n) as u8, (sum[1] / n) as u8, (sum[2] / n) as u8]
+        })
+        .collect()
+}
+
+fn widest_channel(colors: &[[u8; 3]]) -> (usize, u8) {
+    if colors.len() < 2 {
+        return (0, 0);
+    }
+    (0..3)
+        .map(|c| {
+            let min = colors.iter().map(|color| color[c]).min().unwrap();
+            let max = colors.iter().map(|color| color[c]).max().unwrap();
+            (c, max - min)
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap()
+}
+
+/// Maps every pixel to its palette index, optionally with Floyd-Steinberg dithering
+fn quantize(pixels: &[u8], width: usize, height: usize, palette: &[[u8; 3]], dither: bool) -> Vec<u8> {
+    // Nearest palette entry for every colour with 5 bits per channel
+    let mut nearest: Vec<Option<u8>> = vec![None; 1 << 15];
+    let mut lookup = |color: [f32; 3]| -> u8 {
+        let c = |v: f32| (v.clamp(0., 255.) as usize) >> 3;
+        let key = (c(color[0]) << 10) | (c(color[1]) << 5) | c(color[2]);
+        *nearest[key].get_or_insert_with(|| {
+            let distance = |p: &[u8; 3]| -> f32 {
+                (0..3).map(|i| (p[i] as f32 - color[i]).powi(2)).sum()
+            };
+            (0..palette.len())
+                .min_by(|&a, &b| distance(&palette[a]).partial_cmp(&distance(&palette[b])).unwrap())
+                .unwrap_or(0) as u8
+        })
+    };
+
+    let mut indices = vec![0u8; width * height];
+    // Error carried into the current and the next row
+    let mut errors = vec![[0f32; 3]; width + 2];
+    let mut next_errors = vec![[0f32; 3]; width + 2];
+    for y in 0..height {
+        for x in 0..width {
+            let i = (y * width + x) * 4;
+            let mut color = [pixels[i] as f32, pixels[i + 1] as f32, pixels[i + 2] as f32];
+            if dither {
+                for c in 0..3 {
+                    color[c] += errors[x + 1][c];
+                }
+            }
+            let index = lookup(color);
+            indices[y * width + x] = index;
+            if dither {
+                let chosen = palette[index as usize];
+                for c in 0..3 {
+                    let error = color[c] - chosen[c] as f32;
+                    errors[x + 2][c] += error * 7. / 16.;
+                    next_errors[x][c] += error * 3. / 16.;
+                    next_errors[x + 1][c] += error * 5. / 16.;
+                    next_errors[x + 2][c] += error * 1. / 16.;
+                }
+            }
+        }
+        std::mem::swap(&mut errors, &mut next_errors);
+        for e in next_errors.iter_mut() {
+            *e = [0.; 3];
+        }
+    }
+    indices
+}
+
+/// Variable code size LZW as used by gif, packed least significant bit first
+fn lzw(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear: u16 = 1 << min_code_size;
+    let end = clear + 1;
+    let mut size = min_code_size + 1;
+    let mut next = end + 1;
+    let mut dictionary: HashMap<(u16, u8), u16> = HashMap::new();
+
+    let mut out = Vec::new();
+    let mut bits: u32 = 0;
+    let mut count = 0;
+    let mut emit = |code: u16, size: u8, out: &mut Vec<u8>| {
+        bits |= (code as u32) << count;
+        count += size;
+        while count >= 8 {
+            out.push(bits as u8);
+            bits >>= 8;
+            count -= 8;
+        }
+    };
+
+    emit(clear, size, &mut out);
+    if let Some((&first, rest)) = indices.split_first() {
+        let mut prefix = first as u16;
+        for &k in rest {
+            if let Some(&code) = dictionary.get(&(prefix, k)) {
+                prefix = code;
+                continue;
+            }
+            emit(prefix, size, &mut out);
+            if next < 4096 {
+                dictionary.insert((prefix, k), next);
+                // The decoder adds its entries one code later, so grow after the code was used
+                if next == 1 << size && size < 12 {
+                    size += 1;
+                }
+                next += 1;
+            } else {
+                emit(clear, size, &mut out);
+                dictionary.clear();
+                size = min_code_size + 1;
+                next = end + 1;
+            }
+            prefix = k as u16;
+        }
+        emit(prefix, size, &mut out);
+    }
+    emit(end, size, &mut out);
+    emit(0, 7, &mut out); // Flush the last partial byte
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads codes back the way a gif decoder does
+    fn unlzw(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1 << min_code_size;
+        let end = clear + 1;
+        let mut size = min_code_size + 1;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut previous: Option<Vec<u8>> = None;
+        let mut out = Vec::new();
+        let (mut bits, mut count, mut i) = (0u32, 0, 0);
+        loop {
+            while count < size {
+                bits |= (data[i] as u32) << count;
+                count += 8;
+                i += 1;
+            }
+            let code = (bits & ((1 << size) - 1)) as usize;
+            bits >>= size;
+            count -= size;
+
+            if code == clear {
+                // Single values, then placeholders for the clear and end codes
+                table = (0..clear + 2).map(|k| vec![k as u8]).collect();
+                size = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                return out;
+            }
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                // The code being defined right now
+                (None, Some(previous)) => [&previous[..], &previous[..1]].concat(),
+                (None, None) => panic!("code {} before any other", code),
+            };
+            if let Some(previous) = previous {
+                if table.len() < 4096 {
+                    table.push([&previous[..], &entry[..1]].concat());
+                }
+            }
+            if table.len() == 1 << size && size < 12 {
+                size += 1;
+            }
+            out.extend_from_slice(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        assert_eq!(unlzw(&lzw(&[], 8), 8), []);
+        assert_eq!(unlzw(&lzw(&[7], 8), 8), [7]);
+        // Runs define codes as they are used
+        assert_eq!(unlzw(&lzw(&[1; 100], 2), 2), [1; 100]);
+
+        // Enough different strings to fill the dictionary a few times over
+        let mut x: u32 = 1;
+        let indices: Vec<u8> = (0..50000)
+            .map(|i| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                if i % 1000 < 200 { 0 } else { (x >> 16) as u8 }
+            })
+            .collect();
+        assert_eq!(unlzw(&lzw(&indices, 8), 8), indices);
+    }
+
+    #[test]
+    fn few_colours_are_kept() {
+        let colors = [[255, 0, 0], [0, 0, 255], [10, 200, 10]];
+        let pixels: Vec<u8> = (0..12)
+            .flat_map(|i| {
+                let [r, g, b] = colors[i % 3];
+                [r, g, b, 255]
+            })
+            .collect();
+        let palette = palette(&pixels);
+        for color in &colors {
+            assert!(palette.contains(color), "{:?} not in {:?}", color, palette);
+        }
+        let indices = quantize(&pixels, 4, 3, &palette, false);
+        for (i, &index) in indices.iter().enumerate() {
+            assert_eq!(palette[index as usize], colors[i % 3]);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Writers for floating point images, all take rgb floats row by row from the top
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Radiance RGBE, scanlines are written without run length encoding
+pub fn write_hdr(path: &Path, pixels: &[f32], width: u16, height: u16) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    write!(w, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width)?;
+    for p in pixels.chunks(3) {
+        w.write_all(&rgbe(p[0], p[1], p[2]))?;
+    }
+    w.flush()
+}
+
+/// Shared exponent encoding: three 8 bit mantissas scaled by 2^(e - 128)
+fn rgbe(r: f32, g: f32, b: f32) -> [u8; 4] {
+    let v = r.max(g).max(b);
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // v = m * 2^e with m in [0.5, 1)
+    let e = v.log2().floor() as i32 + 1;
+    let scale = 256. / 2f32.powi(e);
+    let c = |x: f32| (x.max(0.) * scale).min(255.) as u8;
+    [c(r), c(g), c(b), (e + 128) as u8]
+}
+
+/// Portable float map, which stores its rows from the bottom
+pub fn write_pfm(path: &Path, pixels: &[f32], width: u16, height: u16) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    // A negative scale marks little endian data
+    write!(w, "PF\n{} {}\n-1.0\n", width, height)?;
+    for row in pixels.chunks(3 * width as usize).rev() {
+        for v in row {
+            w.write_all(&v.to_le_bytes())?;
+        }
+    }
+    w.flush()
+}
+
+/// Single part, uncompressed scanline OpenEXR with 32 bit float channels
+pub fn write_exr(path: &Path, pixels: &[f32], width: u16, height: u16) -> io::Result<()> {
+    let (width, height) = (width as i32, height as i32);
+    let mut header = Vec::new();
+
+    // Channels have to be sorted by name
+    let mut channels = Vec::new();
+    for name in &[b"B", b"G", b"R"] {
+        channels.extend_from_slice(&name[..]);
+        channels.push(0);
+        channels.extend_from_slice(&2i32.to_le_bytes()); // FLOAT
+        channels.extend_from_slice(&[0, 0, 0, 0]); // Not linear, reserved
+        channels.extend_from_slice(&1i32.to_le_bytes()); // x sampling
+        channels.extend_from_slice(&1i32.to_le_bytes()); // y sampling
+    }
+    channels.push(0);
+    attribute(&mut header, "channels", "chlist", &channels);
+    attribute(&mut header, "compression", "compression", &[0]);
+    let mut window = Vec::new();
+    for v in &[0, 0, width - 1, height - 1] {
+        window.extend_from_slice(&v.to_le_bytes());
+    }
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]); // Increasing y
+    attribute(&mut header, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut header, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    header.push(0);
+
+    let mut w = BufWriter::new(File::create(path)?);
+    w.write_all(&[0x76, 0x2F, 0x31, 0x01])?;
+    w.write_all(&2u32.to_le_bytes())?; // Version 2, single part scanline
+    w.write_all(&header)?;
+
+    // Offsets of every scanline, which is one chunk without compression
+    let line_size = 3 * 4 * width as u64;
+    let start = 8 + header.len() as u64 + 8 * height as u64;
+    for y in 0..height as u64 {
+        w.write_all(&(start + y * (8 + line_size)).to_le_bytes())?;
+    }
+
+    for (y, row) in pixels.chunks(3 * width as usize).enumerate() {
+        w.write_all(&(y as i32).to_le_bytes())?;
+        w.write_all(&(line_size as u32).to_le_bytes())?;
+        for channel in &[2, 1, 0] {
+            for p in row.chunks(3) {
+                w.write_all(&p[*channel].to_le_bytes())?;
+            }
+        }
+    }
+    w.flush()
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    /// A 2 × 2 image whose values tell apart the pixels and channels
+    const PIXELS: [f32; 12] = [0., 0.5, 1., 2., 4., 8., 0.25, 0.125, 3., 16., 100., 0.01];
+
+    fn write(writer: fn(&Path, &[f32], u16, u16) -> io::Result<()>, name: &str) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!("hdr-{}-{}", std::process::id(), name));
+        writer(&path, &PIXELS, 2, 2).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    fn float(bytes: &[u8]) -> f32 {
+        f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn int(bytes: &[u8]) -> i32 {
+        i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    #[test]
+    fn rgbe_round_trip() {
+        assert_eq!(rgbe(0., 0., 0.), [0, 0, 0, 0]);
+        assert_eq!(rgbe(1., 0.5, 0.25), [128, 64, 32, 129]);
+        for p in PIXELS.chunks(3) {
+            let [r, g, b, e] = rgbe(p[0], p[1], p[2]);
+            let scale = 2f32.powi(e as i32 - 136);
+            let max = p[0].max(p[1]).max(p[2]);
+            for (&m, &v) in [r, g, b].iter().zip(p) {
+                // The mantissas truncate, so they lose up to one step of the largest channel
+                let decoded = m as f32 * scale;
+                assert!(decoded <= v && v - decoded < max / 128., "{} != {}", decoded, v);
+            }
+        }
+    }
+
+    #[test]
+    fn hdr_layout() {
+        let bytes = write(write_hdr, "test.hdr");
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 2\n";
+        assert_eq!(bytes[..header.len()], header[..]);
+        assert_eq!(bytes.len(), header.len() + 4 * 4);
+        assert_eq!(bytes[header.len()..header.len() + 4], rgbe(0., 0.5, 1.));
+    }
+
+    #[test]
+    fn pfm_layout() {
+        let bytes = write(write_pfm, "test.pfm");
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(bytes[..header.len()], header[..]);
+        let floats: Vec<f32> = bytes[header.len()..].chunks(4).map(float).collect();
+        // Bottom row first
+        assert_eq!(floats[..6], PIXELS[6..]);
+        assert_eq!(floats[6..], PIXELS[..6]);
+    }
+
+    #[test]
+    fn exr_layout() {
+        let bytes = write(write_exr, "test.exr");
+        assert_eq!(bytes[..4], [0x76, 0x2F, 0x31, 0x01]);
+        assert_eq!(int(&bytes[4..]), 2);
+
+        // Attributes are a name, a type, a size and the value, up to an empty name
+        let mut names = Vec::new();
+        let mut i = 8;
+        let text = |i: &mut usize| {
+            let end = *i + bytes[*i..].iter().position(|&b| b == 0).unwrap();
+            let s = String::from_utf8(bytes[*i..end].to_vec()).unwrap();
+            *i = end + 1;
+            s
+        };
+        loop {
+            let name = text(&mut i);
+            if name.is_empty() {
+                break;
+            }
+            text(&mut i);
+            i += 4 + int(&bytes[i..]) as usize;
+            names.push(name);
+        }
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(names, sorted);
+        assert!(names.contains(&"channels".to_string()));
+
+        // Both scanlines, channels sorted by name
+        for y in 0..2 {
+            let offset = u64::from_le_bytes(bytes[i + 8 * y..i + 8 * y + 8].try_into().unwrap()) as usize;
+            assert_eq!(int(&bytes[offset..]), y as i32);
+            assert_eq!(int(&bytes[offset + 4..]), 24);
+            let floats: Vec<f32> = bytes[offset + 8..offset + 32].chunks(4).map(float).collect();
+            let row = &PIXELS[6 * y..6 * y + 6];
+            assert_eq!(floats, [row[2], row[5], row[1], row[4], row[0], row[3]]);
+        }
+        assert_eq!(bytes.len(), i + 2 * 8 + 2 * 32);
+    }
+}
@@ -0,0 +1,180 @@
+use miniquad::*;
+
+use crate::font::GLYPHS;
+
+/// Width and height of a character cell in the font texture, including spacing
+const CELL: (usize, usize) = (6, 8);
+/// Screen pixels per font pixel
+const SCALE: f32 = 2.;
+/// Glyphs and one filled cell for the background
+const CELLS: usize = GLYPHS.len() + 1;
+const MAX_QUADS: usize = 1024;
+
+#[repr(C)]
+struct Vertex {
+    pos: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+/// Text overlay in the top left corner of the window
+pub struct Hud {
+    pipeline: Pipeline,
+    bindings: Bindings,
+    quads: usize,
+    pub visible: bool,
+}
+
+impl Hud {
+    pub fn new(ctx: &mut Context) -> Hud {
+        let width = CELL.0 * CELLS;
+        let mut atlas = vec![0u8; width * CELL.1 * 4];
+        for (i, glyph) in GLYPHS.iter().enumerate() {
+            for (y, row) in glyph.iter().enumerate() {
+                for x in 0..5 {
+                    if row & (0x10 >> x) != 0 {
+                        let p = (y * width + i * CELL.0 + x) * 4;
+                        atlas[p..p + 4].copy_from_slice(&[0xFF; 4]);
+                    }
+                }
+            }
+        }
+        for y in 0..CELL.1 {
+            for x in 0..CELL.0 {
+                let p = (y * width + GLYPHS.len() * CELL.0 + x) * 4;
+                atlas[p..p + 4].copy_from_slice(&[0xFF; 4]);
+            }
+        }
+        let texture = Texture::from_rgba8(ctx, width as u16, CELL.1 as u16, &atlas);
+        texture.set_filter(ctx, FilterMode::Nearest);
+
+        let vertex_buffer = Buffer::stream(
+            ctx,
+            BufferType::VertexBuffer,
+            MAX_QUADS * 4 * std::mem::size_of::<Vertex>(),
+        );
+        let indices: Vec<u16> = (0..MAX_QUADS as u16)
+            .flat_map(|q| vec![0, 1, 2, 0, 2, 3].into_iter().map(move |i| q * 4 + i))
+            .collect();
+        let index_buffer = Buffer::immutable(ctx, BufferType::IndexBuffer, &indices);
+
+        let bindings = Bindings {
+            vertex_buffers: vec![vertex_buffer],
+            index_buffer,
+            images: vec![texture],
+        };
+
+        let shader = Shader::new(ctx, shader::VERTEX, shader::FRAGMENT, shader::meta()).unwrap();
+        let pipeline = Pipeline::with_params(
+            ctx,
+            &[BufferLayout::default()],
+            &[
+                VertexAttribute::new("pos", VertexFormat::Float2),
+                VertexAttribute::new("uv", VertexFormat::Float2),
+                VertexAttribute::new("color", VertexFormat::Float4),
+            ],
+            shader,
+            PipelineParams {
+                color_blend: Some(BlendState::new(
+                    Equation::Add,
+                    BlendFactor::Value(BlendValue::SourceAlpha),
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+                )),
+                ..Default::default()
+            },
+        );
+
+        Hud {
+            pipeline,
+            bindings,
+            quads: 0,
+            visible: true,
+        }
+    }
+
+    /// Lays out the lines on a dark background, characters outside of ascii show as `?`
+    pub fn set_text(&mut self, ctx: &mut Context, lines: &[String]) {
+        let (screen_width, screen_height) = ctx.screen_size();
+        // From pixels with the origin in the top left to normalized device coordinates
+        let ndc = |x: f32, y: f32| [x / screen_width * 2. - 1., 1. - y / screen_height * 2.];
+        let mut vertices = Vec::new();
+        let mut quad = |x: f32, y: f32, w: f32, h: f32, cell: usize, color: [f32; 4]| {
+            let u0 = (cell * CELL.0) as f32 / (CELL.0 * CELLS) as f32;
+            let u1 = ((cell + 1) * CELL.0) as f32 / (CELL.0 * CELLS) as f32;
+            vertices.push(Vertex { pos: ndc(x, y + h), uv: [u0, 1.], color });
+            vertices.push(Vertex { pos: ndc(x + w, y + h), uv: [u1, 1.], color });
+            vertices.push(Vertex { pos: ndc(x + w, y), uv: [u1, 0.], color });
+            vertices.push(Vertex { pos: ndc(x, y), uv: [u0, 0.], color });
+        };
+
+        let (cell_width, cell_height) = (CELL.0 as f32 * SCALE, CELL.1 as f32 * SCALE);
+        let columns = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let margin = 2. * SCALE;
+        quad(
+            0.,
+            0.,
+            columns as f32 * cell_width + 2. * margin,
+            lines.len() as f32 * cell_height + 2. * margin,
+            GLYPHS.len(),
+            [0., 0., 0., 0.6],
+        );
+        for (row, line) in lines.iter().enumerate() {
+            for (column, c) in line.chars().enumerate() {
+                let c = if (' '..='~').contains(&c) { c } else { '?' };
+                quad(
+                    margin + column as f32 * cell_width,
+                    margin + row as f32 * cell_height,
+                    cell_width,
+                    cell_height,
+                    c as usize - ' ' as usize,
+                    [1., 1., 1., 1.],
+                );
+            }
+        }
+
+        vertices.truncate(MAX_QUADS * 4);
+        self.quads = vertices.len() / 4;
+        self.bindings.vertex_buffers[0].update(ctx, &vertices);
+    }
+
+    /// Draws into the current render pass
+    pub fn draw(&self, ctx: &mut Context) {
+        if !self.visible {
+            return;
+        }
+        ctx.apply_pipeline(&self.pipeline);
+        ctx.apply_bindings(&self.bindings);
+        ctx.draw(0, self.quads as i32 * 6, 1);
+    }
+}
+
+mod shader {
+    use miniquad::*;
+
+    pub const VERTEX: &str = r#"#version 100
+    attribute vec2 pos;
+    attribute vec2 uv;
+    attribute vec4 color;
+    varying lowp vec2 texcoord;
+    varying lowp vec4 tint;
+    void main() {
+        gl_Position = vec4(pos, 0, 1);
+        texcoord = uv;
+        tint = color;
+    }"#;
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying lowp vec2 texcoord;
+    varying lowp vec4 tint;
+    uniform sampler2D tex;
+    void main() {
+        gl_FragColor = texture2D(tex, texcoord) * tint;
+    }"#;
+
+    pub fn meta() -> ShaderMeta {
+        ShaderMeta {
+            images: vec!["tex".to_string()],
+            uniforms: UniformBlockLayout { uniforms: vec![] },
+        }
+    }
+}
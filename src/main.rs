@@ -1,16 +1,43 @@
+#[macro_use]
+mod log;
+
 mod raytracer;
 use std::f64::consts::PI;
 
-use png_renderer::PngRenderer;
-use raytracer::Raytracer;
+use options::Options;
+use png_renderer::{PngRenderer, Sequence};
+use raytracer::{CameraPath, Raytracer, SceneFile};
+use stream_renderer::{StreamFormat, StreamRenderer};
 
 mod renderer;
-use renderer::Renderer;
+use renderer::{Pass, Renderer};
 
 mod png_renderer;
 
+mod options;
+
+mod apng;
+mod gif;
+
+mod stream_renderer;
+
+mod hdr;
+
 mod util;
 
+mod controls;
+use controls::Controls;
+
+mod timeline;
+use timeline::Timeline;
+
+mod capture;
+use capture::Capture;
+
+mod font;
+mod hud;
+use hud::Hud;
+
 use miniquad::*;
 
 // TODO Choose by commandline args + make dynamic
@@ -32,10 +59,21 @@ struct Stage<R: Renderer> {
     pipeline: Pipeline,
     bindings: Bindings,
     renderer: R,
+    /// The buffer that is shown
+    pass: Pass,
+    controls: Controls,
+    timeline: Timeline,
+    capture: Capture,
+    hud: Hud,
+    /// The pixels currently on screen with width and height
+    shown: (Vec<u8>, u16, u16),
+    /// Time of the last update in seconds
+    last_update: f64,
 }
 
 impl<R: Renderer> Stage<R> {
-    pub fn new(ctx: &mut Context, renderer: R) -> Stage<R> {
+    /// Shows the animation from `t`, which starts at `start`
+    pub fn new(ctx: &mut Context, renderer: R, t: f64, start: f64) -> Stage<R> {
         #[rustfmt::skip]
         let vertices: [Vertex; 4] = [
             Vertex { pos: Vec2 { x: -1., y: -1. }, uv: Vec2 { x: 0., y: 1. } },
@@ -75,16 +113,64 @@ impl<R: Renderer> Stage<R> {
             pipeline,
             bindings,
             renderer,
+            pass: Pass::Beauty,
+            controls: Controls::new(),
+            timeline: Timeline::new(t, start, date::now()),
+            capture: Capture::new(),
+            hud: Hud::new(ctx),
+            shown: (pixels, WIDTH, HEIGHT),
+            last_update: date::now(),
         }
     }
+
+    /// Statistics shown in the HUD, `frame_time` is the time the last render took in seconds
+    fn hud_lines(&mut self, frame_time: f64) -> Vec<String> {
+        let stats = self.renderer.stats();
+        let timeline = &self.timeline;
+        let mut lines = vec![
+            format!("frame: {:.1} ms", frame_time * 1000.),
+            format!("rays/s: {:.2}M", stats.rays as f64 / frame_time / 1e6),
+            format!("samples: {}", stats.samples),
+            format!(
+                "t: {:.3} speed: {}x{}",
+                timeline.t,
+                timeline.speed,
+                if timeline.paused { " paused" } else { "" }
+            ),
+        ];
+        if let Some(camera) = self.renderer.camera() {
+            let p = camera.pos;
+            lines.push(format!("camera: {:.1} {:.1} {:.1} {:?}", p.x, p.y, p.z, self.controls.mode));
+        }
+        lines.push(format!("view: {}", self.pass.name()));
+        lines
+    }
 }
 
 impl<R: Renderer> EventHandler for Stage<R> {
     fn update(&mut self, ctx: &mut Context) {
-        let t = date::now();
+        let now = date::now();
+        if let Some(camera) = self.renderer.camera() {
+            self.controls.update(camera, now - self.last_update);
+        }
+        self.last_update = now;
 
-        let (pixels, width, height) = self.renderer.render(t);
-        self.bindings.images[0].update(ctx, &pixels);
+        let t = self.timeline.advance(now);
+        let start = date::now();
+        let (mut pixels, width, height) = self.renderer.render(t);
+        let frame_time = date::now() - start;
+        if let Some(buffer) = self.renderer.pass(self.pass) {
+            pixels = self.pass.show(&buffer);
+        }
+        let texture = &mut self.bindings.images[0];
+        if (texture.width, texture.height) != (width as u32, height as u32) {
+            texture.delete();
+            *texture = Texture::from_rgba8(ctx, width, height, &pixels);
+            texture.set_filter(ctx, FilterMode::Nearest);
+        }
+        texture.update(ctx, &pixels);
+        self.capture.record(&pixels, width, height);
+        self.shown = (pixels, width, height);
 
         let r2 = width as f32 / height as f32;
         let (screen_width, screen_height) = ctx.screen_size();
@@ -120,8 +206,85 @@ impl<R: Renderer> EventHandler for Stage<R> {
         ];
         self.bindings.vertex_buffers[0].update(ctx, &vertices);
 
-        let dt = date::now() - t;
-        println!("Update took {}s", dt);
+        if self.hud.visible {
+            let lines = self.hud_lines(frame_time);
+            self.hud.set_text(ctx, &lines);
+        }
+
+        let dt = date::now() - now;
+        debug!("Update took {}s", dt);
+    }
+
+    fn key_down_event(&mut self, _ctx: &mut Context, keycode: KeyCode, keymods: KeyMods, _repeat: bool) {
+        match keycode {
+            KeyCode::F12 if keymods.shift => {
+                let (pixels, width, height) = self.renderer.render_offline(self.timeline.t);
+                self.capture.screenshot(&pixels, width, height);
+                return;
+            }
+            KeyCode::F12 => {
+                let (pixels, width, height) = &self.shown;
+                self.capture.screenshot(pixels, *width, *height);
+                return;
+            }
+            KeyCode::R => {
+                self.capture.toggle_recording();
+                return;
+            }
+            KeyCode::H => {
+                self.hud.visible = !self.hud.visible;
+                return;
+            }
+            _ => {}
+        }
+
+        let pass = match keycode {
+            KeyCode::Tab => self.pass.next(),
+            KeyCode::F1 => Pass::Beauty,
+            KeyCode::F2 => Pass::Normal,
+            KeyCode::F3 => Pass::Depth,
+            KeyCode::F4 => Pass::Bounces,
+            KeyCode::F5 => Pass::ObjectId,
+            KeyCode::F6 => Pass::Tests,
+            KeyCode::F7 => Pass::Albedo,
+            KeyCode::F8 => Pass::Position,
+            KeyCode::F9 => Pass::Uv,
+            _ => {
+                if self.timeline.key_down(keycode) {
+                    return;
+                }
+                if let Some(camera) = self.renderer.camera() {
+                    self.controls.key_down(camera, keycode);
+                }
+                return;
+            }
+        };
+        self.pass = pass;
+        info!("Showing {} pass", self.pass.name());
+    }
+
+    fn key_up_event(&mut self, _ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods) {
+        self.controls.key_up(keycode);
+    }
+
+    fn mouse_button_down_event(&mut self, _ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        self.controls.mouse_down(button, x, y);
+    }
+
+    fn mouse_button_up_event(&mut self, _ctx: &mut Context, _button: MouseButton, _x: f32, _y: f32) {
+        self.controls.mouse_up();
+    }
+
+    fn mouse_motion_event(&mut self, _ctx: &mut Context, x: f32, y: f32) {
+        if let Some(camera) = self.renderer.camera() {
+            self.controls.mouse_motion(camera, x, y);
+        }
+    }
+
+    fn mouse_wheel_event(&mut self, _ctx: &mut Context, _x: f32, y: f32) {
+        if let Some(camera) = self.renderer.camera() {
+            self.controls.mouse_wheel(camera, y);
+        }
     }
 
     fn draw(&mut self, ctx: &mut Context) {
@@ -131,21 +294,106 @@ impl<R: Renderer> EventHandler for Stage<R> {
         ctx.apply_pipeline(&self.pipeline);
         ctx.apply_bindings(&self.bindings);
         ctx.draw(0, 6, 1);
+        self.hud.draw(ctx);
         ctx.end_render_pass();
 
         ctx.commit_frame();
         let dt = date::now() - t;
-        println!("Render took {}s", dt);
+        debug!("Render took {}s", dt);
     }
 }
 
 fn main() {
-    // TODO: Choose by command line args
-    let raytracer = Raytracer::new();
-    let png_renderer = PngRenderer::new(Raytracer::new(), 0., 2.*PI);
-    miniquad::start(conf::Conf::default(), |mut ctx| {
-        UserData::owning(Stage::new(&mut ctx, raytracer), ctx)
+    let options = Options::parse();
+    log::set_level(options.log);
+    let camera_path = options.camera_path.as_ref().map(|path| match CameraPath::load(path) {
+        Ok(camera_path) => camera_path,
+        Err(e) => panic!("{}: {}", path.display(), e),
+    });
+    let scene_file = options.scene.as_ref().map(|path| match SceneFile::load(path) {
+        Ok(scene_file) => scene_file,
+        Err(e) => panic!("{}: {}", path.display(), e),
+    });
+    let terrain = options.terrain.as_ref().map(|path| match Raytracer::terrain(path) {
+        Ok(terrain) => terrain,
+        Err(e) => panic!("{}: {}", path.display(), e),
     });
+    // The animation lasts as long as the camera path
+    let (start, end) = camera_path.as_ref().map_or((0., 2. * PI), |path| path.duration());
+    let sequence = Sequence {
+        shard: options.shard,
+        shards: options.shards,
+        verify: options.verify,
+        format: options.format,
+        passes: options.passes,
+        ..Sequence::new(start, end)
+    };
+
+    let (lens, shutter, projection, fov) = (options.lens, options.shutter, options.projection, options.fov);
+    let (stereo, showcase) = (options.stereo, options.showcase);
+    let raytracer = move || {
+        let mut raytracer = Raytracer::new();
+        raytracer.stereo = stereo;
+        if let Some(camera_path) = &camera_path {
+            raytracer.camera_path = camera_path.clone();
+        }
+        if let Some(scene_file) = &scene_file {
+            raytracer.add(scene_file);
+        }
+        if let Some(terrain) = &terrain {
+            raytracer.set_terrain(terrain.clone());
+        }
+        if showcase {
+            raytracer.showcase();
+        }
+        let camera = raytracer.camera().unwrap();
+        camera.lens = lens;
+        camera.projection = projection;
+        if let Some(fov) = fov {
+            camera.fov = fov;
+        }
+        raytracer.shutter = shutter;
+        raytracer
+    };
+
+    if let Some(path) = &options.gif {
+        gif::write_gif(&mut raytracer(), sequence.frames, path, options.dither, options.loops).unwrap();
+        return;
+    }
+
+    if let Some(path) = &options.apng {
+        apng::write_apng(&mut raytracer(), sequence.frames, path, options.loops).unwrap();
+        return;
+    }
+
+    if options.stdout || options.out.is_some() {
+        let out: Box<dyn std::io::Write> = match &options.out {
+            Some(path) => Box::new(std::io::BufWriter::new(std::fs::File::create(path).unwrap())),
+            None => Box::new(std::io::BufWriter::new(std::io::stdout())),
+        };
+        let format = if options.raw { StreamFormat::Rgba } else { StreamFormat::Y4m };
+        let mut stream = StreamRenderer::new(raytracer(), out, format, sequence.frames.fps);
+        stream.render_all(sequence.frames);
+        return;
+    }
+
+    if options.headless {
+        sequence.render_parallel(raytracer, options.threads);
+        return;
+    }
+
+    let time = options.time;
+    if options.png {
+        let png_renderer = PngRenderer::new(raytracer(), sequence);
+        miniquad::start(conf::Conf::default(), move |mut ctx| {
+            UserData::owning(Stage::new(&mut ctx, png_renderer, time, start), ctx)
+        });
+    } else {
+        let raytracer = raytracer();
+        miniquad::start(conf::Conf::default(), move |mut ctx| {
+            UserData::owning(Stage::new(&mut ctx, raytracer, time, start), ctx)
+        });
+    }
 }
 
 mod shader {
@@ -0,0 +1,220 @@
+use std::env;
+use std::path::PathBuf;
+
+use crate::log::Level;
+use crate::png_renderer::Format;
+use crate::raytracer::{Fov, Layout, Lens, Projection, Stereo};
+
+/// Settings chosen on the command line
+pub struct Options {
+    /// Render the animation to a png sequence instead of previewing it
+    pub png: bool,
+    /// Render without opening a window
+    pub headless: bool,
+    pub shard: u64,
+    pub shards: u64,
+    pub threads: usize,
+    /// Re-render existing frames whose manifest doesn't match the scene
+    pub verify: bool,
+    /// File format of the image sequence
+    pub format: Format,
+    /// Write auxiliary passes next to the images of the sequence
+    pub passes: bool,
+    /// Render the animation to an animated gif at this path
+    pub gif: Option<PathBuf>,
+    /// Render the animation to an animated png at this path
+    pub apng: Option<PathBuf>,
+    /// Dither gif frames instead of mapping to the nearest colour
+    pub dither: bool,
+    /// How often animations repeat after playing once, 0 repeats forever
+    pub loops: u32,
+    /// Stream the animation as video to stdout
+    pub stdout: bool,
+    /// Stream the animation as video to this file
+    pub out: Option<PathBuf>,
+    /// Stream plain rgba frames instead of YUV4MPEG2
+    pub raw: bool,
+    /// Animation time the viewer starts at
+    pub time: f64,
+    pub log: Level,
+    /// Depth of field of the camera
+    pub lens: Lens,
+    /// Fraction of a frame the shutter stays open for motion blur
+    pub shutter: f64,
+    pub projection: Projection,
+    /// Replaces the field of view of the scene
+    pub fov: Option<Fov>,
+    pub stereo: Option<Stereo>,
+    /// Keyframes for the animated camera
+    pub camera_path: Option<PathBuf>,
+    /// Animated spheres and sky added to the scene
+    pub scene: Option<PathBuf>,
+    /// Grayscale heightmap that replaces the floor
+    pub terrain: Option<PathBuf>,
+    /// Adds an example of every kind of object to the scene
+    pub showcase: bool,
+}
+
+impl Options {
+    pub fn parse() -> Options {
+        let mut options = Options {
+            png: false,
+            headless: false,
+            shard: 0,
+            shards: 1,
+            threads: thread_count(),
+            verify: false,
+            format: Format::Png8,
+            passes: false,
+            gif: None,
+            apng: None,
+            dither: false,
+            loops: 0,
+            stdout: false,
+            out: None,
+            raw: false,
+            time: 0.,
+            log: Level::Info,
+            // Focused on the centre of the scene as seen from the animated camera
+            lens: Lens {
+                focus: 200.,
+                ..Lens::PINHOLE
+            },
+            shutter: 0.,
+            projection: Projection::Perspective,
+            fov: None,
+            stereo: None,
+            camera_path: None,
+            scene: None,
+            terrain: None,
+            showcase: false,
+        };
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--png" => options.png = true,
+                "--headless" => options.headless = true,
+                "--verify" => options.verify = true,
+                "--showcase" => options.showcase = true,
+                "--shard" => {
+                    // Given as `index/total`, e.g. `--shard 0/4`
+                    let value = args.next().expect("--shard needs a value like 0/4");
+                    let mut parts = value.splitn(2, '/');
+                    options.shard = parts.next().unwrap().parse().expect("Invalid shard index");
+                    options.shards = parts
+                        .next()
+                        .expect("--shard needs a value like 0/4")
+                        .parse()
+                        .expect("Invalid shard count");
+                    if options.shards == 0 || options.shard >= options.shards {
+                        panic!("Shard {} doesn't exist", value);
+                    }
+                }
+                "--threads" => {
+                    let value = args.next().expect("--threads needs a value");
+                    options.threads = value.parse().expect("Invalid thread count");
+                }
+                "--format" => {
+                    let value = args.next().expect("--format needs a value");
+                    options.format = Format::parse(&value).expect("Format must be png, png16, hdr, pfm or exr");
+                }
+                "--passes" => options.passes = true,
+                "--gif" => options.gif = Some(args.next().expect("--gif needs a path").into()),
+                "--apng" => options.apng = Some(args.next().expect("--apng needs a path").into()),
+                "--dither" => options.dither = true,
+                "--loops" => {
+                    let value = args.next().expect("--loops needs a value");
+                    options.loops = value.parse().expect("Invalid loop count");
+                }
+                "--stdout" => options.stdout = true,
+                "--out" => options.out = Some(args.next().expect("--out needs a path").into()),
+                "--raw" => options.raw = true,
+                "--time" => {
+                    let value = args.next().expect("--time needs a value");
+                    options.time = value.parse().expect("Invalid time");
+                }
+                "--log" => {
+                    let value = args.next().expect("--log needs a level");
+                    options.log = Level::parse(&value).expect("Log level must be quiet, info or debug");
+                }
+                "--aperture" => {
+                    let value = args.next().expect("--aperture needs a radius");
+                    options.lens.aperture = value.parse().expect("Invalid aperture");
+                }
+                "--focus" => {
+                    let value = args.next().expect("--focus needs a distance");
+                    options.lens.focus = value.parse().expect("Invalid focus distance");
+                    // Rays are scaled by it, so 0 turns them into NaN and negative values flip them
+                    if options.lens.focus.is_nan() || options.lens.focus <= 0. {
+                        panic!("Focus distance {} isn't positive", value);
+                    }
+                }
+                "--blades" => {
+                    let value = args.next().expect("--blades needs a count");
+                    options.lens.blades = value.parse().expect("Invalid blade count");
+                }
+                "--shutter" => {
+                    let value = args.next().expect("--shutter needs a fraction of a frame");
+                    options.shutter = value.parse().expect("Invalid shutter");
+                }
+                "--projection" => {
+                    let value = args.next().expect("--projection needs a name");
+                    options.projection = Projection::parse(&value)
+                        .expect("Projection must be perspective, orthographic, fisheye, equirectangular or cubemap");
+                }
+                "--fov" => {
+                    let value = args.next().expect("--fov needs degrees");
+                    options.fov = Some(Fov::Horizontal(value.parse().expect("Invalid field of view")));
+                }
+                "--vfov" => {
+                    let value = args.next().expect("--vfov needs degrees");
+                    options.fov = Some(Fov::Vertical(value.parse().expect("Invalid field of view")));
+                }
+                "--stereo" => {
+                    let value = args.next().expect("--stereo needs a layout");
+                    let layout = Layout::parse(&value).expect("Stereo layout must be side-by-side, top-bottom or anaglyph");
+                    options.stereo = Some(Stereo {
+                        layout,
+                        ..options.stereo.unwrap_or(DEFAULT_STEREO)
+                    });
+                }
+                "--ipd" => {
+                    let value = args.next().expect("--ipd needs a distance");
+                    let stereo = options.stereo.get_or_insert(DEFAULT_STEREO);
+                    stereo.ipd = value.parse().expect("Invalid interpupillary distance");
+                }
+                "--convergence" => {
+                    let value = args.next().expect("--convergence needs a distance");
+                    let stereo = options.stereo.get_or_insert(DEFAULT_STEREO);
+                    stereo.convergence = value.parse().expect("Invalid convergence distance");
+                    // The images of the eyes shift by the eye offset divided by it
+                    if stereo.convergence.is_nan() || stereo.convergence <= 0. {
+                        panic!("Convergence distance {} isn't positive", value);
+                    }
+                }
+                "--camera-path" => {
+                    options.camera_path = Some(args.next().expect("--camera-path needs a path").into());
+                }
+                "--scene" => options.scene = Some(args.next().expect("--scene needs a path").into()),
+                "--terrain" => {
+                    options.terrain = Some(args.next().expect("--terrain needs a path").into());
+                }
+                _ => panic!("Unknown argument: {}", arg),
+            }
+        }
+        options
+    }
+}
+
+/// Eyes a few units apart, converging on the centre of the scene
+const DEFAULT_STEREO: Stereo = Stereo {
+    layout: Layout::SideBySide,
+    ipd: 6.4,
+    convergence: 200.,
+};
+
+fn thread_count() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
@@ -1,68 +1,284 @@
-use std::path::Path;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
 
-use crate::{renderer::Renderer, HEIGHT, WIDTH};
+use crate::hdr;
+use crate::raytracer::Camera;
+use crate::renderer::{to_rgba8, FrameRange, Pass, Renderer, Stats, FPS};
+use crate::{HEIGHT, WIDTH};
 
-pub struct PngRenderer<T> where T: Renderer {
-    renderer: T,
-    start: f64,
-    end: f64,
-    steps: u64,
-    stepsize: f64,
-    current: u64,
-    done: bool,
+const DIRECTORY: &str = "images";
+
+/// File format of the images in a sequence
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Png8,
+    Png16,
+    /// Radiance RGBE
+    Hdr,
+    /// Portable float map
+    Pfm,
+    Exr,
 }
 
-impl<T> PngRenderer<T> where T: Renderer {
-    fn save_image(bytes: &Vec<u8>, step: u64, steps: u64) {
-        let width = f64::log10(steps as f64).floor();
-        if width > 5. {
-            panic!("We can't handle more than 10000 images :(");
+impl Format {
+    pub fn parse(name: &str) -> Option<Format> {
+        match name {
+            "png" => Some(Format::Png8),
+            "png16" => Some(Format::Png16),
+            "hdr" => Some(Format::Hdr),
+            "pfm" => Some(Format::Pfm),
+            "exr" => Some(Format::Exr),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Png8 | Format::Png16 => "png",
+            Format::Hdr => "hdr",
+            Format::Pfm => "pfm",
+            Format::Exr => "exr",
+        }
+    }
+}
+
+/// The frames of an animation as numbered images in `images/`
+///
+/// A sequence can be split into shards, e.g. one per machine, and frames that
+/// were already written are skipped, so an interrupted render can be resumed.
+#[derive(Clone, Copy, Debug)]
+pub struct Sequence {
+    pub frames: FrameRange,
+    /// Only frames with `frame % shards == shard` are rendered
+    pub shard: u64,
+    pub shards: u64,
+    /// Only skip existing frames if their manifest matches the current scene
+    pub verify: bool,
+    pub format: Format,
+    /// Also write the auxiliary passes of every frame next to the image
+    pub passes: bool,
+}
+
+impl Sequence {
+    pub fn new(start: f64, end: f64) -> Sequence {
+        let frames = FrameRange::new(start, end, FPS);
+        if frames.count() > 100000 {
+            panic!("We can't handle more than 100000 images :(");
+        }
+        Sequence {
+            frames,
+            shard: 0,
+            shards: 1,
+            verify: false,
+            format: Format::Png8,
+            passes: false,
+        }
+    }
+
+    fn image_path(&self, frame: u64, pass: Pass) -> PathBuf {
+        let name = match pass {
+            Pass::Beauty => format!("image-{:0>5}", frame),
+            _ => format!("image-{:0>5}.{}", frame, pass.name()),
+        };
+        PathBuf::from(format!("{}/{}.{}", DIRECTORY, name, self.format.extension()))
+    }
+
+    fn manifest_path(&self, frame: u64) -> PathBuf {
+        self.image_path(frame, Pass::Beauty).with_extension("manifest")
+    }
+
+    fn manifest(&self, frame: u64, hash: u64) -> String {
+        format!("t={}\nhash={:016x}\n", self.frames.time(frame), hash)
+    }
+
+    /// Whether the frame belongs to this shard and still has to be rendered
+    pub fn pending(&self, frame: u64, hash: u64) -> bool {
+        if frame % self.shards != self.shard {
+            return false;
+        }
+        if !self.image_path(frame, Pass::Beauty).exists() {
+            return true;
+        }
+        if !self.verify {
+            return false;
+        }
+        match fs::read_to_string(self.manifest_path(frame)) {
+            Ok(manifest) => manifest != self.manifest(frame, hash),
+            Err(_) => true,
+        }
+    }
+
+    /// Renders a frame and writes it with its manifest, returns the rgba image
+    ///
+    /// Images are written under a temporary name first, so an interrupted
+    /// write never leaves a truncated file that would be skipped on resume.
+    pub fn render_frame<T: Renderer>(&self, renderer: &mut T, frame: u64, hash: u64) -> (Vec<u8>, u16, u16) {
+        fs::create_dir_all(DIRECTORY).unwrap();
+        let t = self.frames.time(frame);
+
+        let r = if self.format == Format::Png8 {
+            let r = renderer.render(t);
+            let part = self.image_path(frame, Pass::Beauty).with_extension("part");
+            save_image(&part, &r.0, r.1, r.2, png::BitDepth::Eight);
+            fs::rename(&part, self.image_path(frame, Pass::Beauty)).unwrap();
+            r
+        } else {
+            let (pixels, width, height) = renderer.render_hdr(t);
+            self.write(frame, Pass::Beauty, &pixels, width, height);
+            (to_rgba8(&pixels), width, height)
+        };
+
+        if self.passes {
+            for pass in Pass::AUXILIARY.iter() {
+                if let Some(pixels) = renderer.pass(*pass) {
+                    self.write(frame, *pass, &pixels, r.1, r.2);
+                }
+            }
         }
-        let pathname = format!("images/image-{:0>5}.png", step);
-        let path = Path::new(&pathname);
-        println!("Opening file: {:?}", path);
-        let file = File::create(path).unwrap();
-        println!("Rendering to: {:?}", file);
-        let ref mut w = BufWriter::new(file);
 
-        let mut encoder = png::Encoder::new(w, WIDTH.into(), HEIGHT.into());
-        encoder.set_color(png::ColorType::RGBA);
-        encoder.set_depth(png::BitDepth::Eight);
-        let mut writer = encoder.write_header().unwrap();
+        fs::write(self.manifest_path(frame), self.manifest(frame, hash)).unwrap();
+        r
+    }
 
-        writer.write_image_data(bytes).unwrap();
-        println!("Render done");
+    /// Writes a float image, png formats get the pass mapped into their range
+    fn write(&self, frame: u64, pass: Pass, pixels: &[f32], width: u16, height: u16) {
+        let path = self.image_path(frame, pass);
+        let part = path.with_extension("part");
+        match self.format {
+            Format::Png8 => {
+                let bytes = to_rgba8(&pass.normalize(pixels));
+                save_image(&part, &bytes, width, height, png::BitDepth::Eight)
+            }
+            Format::Png16 => {
+                let bytes = to_rgba16(&pass.normalize(pixels));
+                save_image(&part, &bytes, width, height, png::BitDepth::Sixteen)
+            }
+            Format::Hdr => hdr::write_hdr(&part, pixels, width, height).unwrap(),
+            Format::Pfm => hdr::write_pfm(&part, pixels, width, height).unwrap(),
+            Format::Exr => hdr::write_exr(&part, pixels, width, height).unwrap(),
+        }
+        fs::rename(&part, &path).unwrap();
+    }
+
+    /// Renders all pending frames without a window
+    ///
+    /// Every thread builds its own renderer with `make` and takes the next
+    /// frame that is not yet claimed until the sequence is done.
+    pub fn render_parallel<T, F>(&self, make: F, threads: usize)
+    where
+        T: Renderer,
+        F: Fn() -> T + Clone + Send + 'static,
+    {
+        let sequence = *self;
+        let next = Arc::new(AtomicU64::new(0));
+        let handles: Vec<_> = (0..threads.max(1))
+            .map(|_| {
+                let next = next.clone();
+                let make = make.clone();
+                thread::spawn(move || {
+                    let mut renderer = make();
+                    let hash = renderer.scene_hash();
+                    loop {
+                        let frame = next.fetch_add(1, Ordering::SeqCst);
+                        if frame >= sequence.frames.count() {
+                            break;
+                        }
+                        if !sequence.pending(frame, hash) {
+                            continue;
+                        }
+                        sequence.render_frame(&mut renderer, frame, hash);
+                        info!("Frame {} of {} done", frame, sequence.frames.steps);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
     }
 }
 
+/// Converts rgb floats to big endian 16 bit rgba
+fn to_rgba16(pixels: &[f32]) -> Vec<u8> {
+    let c = |v: f32| ((v.clamp(0., 1.) * 65535.).round() as u16).to_be_bytes();
+    let mut bytes = Vec::with_capacity(pixels.len() / 3 * 8);
+    for p in pixels.chunks(3) {
+        for v in &[c(p[0]), c(p[1]), c(p[2]), [0xFF, 0xFF]] {
+            bytes.extend_from_slice(v);
+        }
+    }
+    bytes
+}
+
+pub fn save_image(path: &Path, bytes: &[u8], width: u16, height: u16, depth: png::BitDepth) {
+    debug!("Opening file: {:?}", path);
+    let file = File::create(path).unwrap();
+    debug!("Rendering to: {:?}", file);
+    let ref mut w = BufWriter::new(file);
+
+    let mut encoder = png::Encoder::new(w, width.into(), height.into());
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(depth);
+    let mut writer = encoder.write_header().unwrap();
+
+    writer.write_image_data(bytes).unwrap();
+    debug!("Render done");
+}
+
+pub struct PngRenderer<T> where T: Renderer {
+    renderer: T,
+    pub sequence: Sequence,
+    current: u64,
+    done: bool,
+}
+
 impl<T> PngRenderer<T> where T: Renderer {
-    pub fn new(renderer: T, start: f64, end: f64) -> PngRenderer<T> {
-        let fps = 30.;
-        let interval = end - start;
-        let steps = (interval * fps).round() as u64;
-        let stepsize = interval / (steps as f64);
+    pub fn new(renderer: T, sequence: Sequence) -> PngRenderer<T> {
         PngRenderer {
-            renderer, start, end, steps, current: 0, stepsize, done: false,
+            renderer, sequence, current: 0, done: false,
         }
     }
 }
 
 impl<T> Renderer for PngRenderer<T> where T: Renderer{
-    fn render(&mut self, t: f64) -> (Vec<u8>, u16, u16) {
-        println!("Step: {} of {}, done: {}", self.current, self.steps, self.done);
+    fn render(&mut self, _t: f64) -> (Vec<u8>, u16, u16) {
+        let hash = self.renderer.scene_hash();
+        while !self.done && !self.sequence.pending(self.current, hash) {
+            self.current += 1;
+            self.done = self.current >= self.sequence.frames.count();
+        }
+        debug!("Step: {} of {}, done: {}", self.current, self.sequence.frames.steps, self.done);
         if self.done {
             return (vec![0xAA; 4 * WIDTH as usize * HEIGHT as usize], WIDTH, HEIGHT);
         }
-        let r = self.renderer.render(self.current as f64 * self.stepsize + self.start);
-        PngRenderer::<T>::save_image(&r.0, self.current, self.steps);
+        let r = self.sequence.render_frame(&mut self.renderer, self.current, hash);
 
         self.current += 1;
-        if self.current > self.steps {
-            self.current = 0;
-            self.done = true;
-        }
+        self.done = self.current >= self.sequence.frames.count();
         r
     }
-}
\ No newline at end of file
+
+    fn render_offline(&mut self, t: f64) -> (Vec<u8>, u16, u16) {
+        self.renderer.render_offline(t)
+    }
+
+    fn pass(&self, pass: Pass) -> Option<Vec<f32>> {
+        self.renderer.pass(pass)
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        self.renderer.camera()
+    }
+
+    fn scene_hash(&self) -> u64 {
+        self.renderer.scene_hash()
+    }
+
+    fn stats(&self) -> Stats {
+        self.renderer.stats()
+    }
+}
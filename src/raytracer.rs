@@ -1,47 +1,62 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
 use std::{cmp::Ordering, f64::consts::PI};
 
+mod animated;
+mod camera;
+mod camera_path;
+mod csg;
+mod cuboid;
+mod disk;
+mod expression;
+mod fingerprint;
+mod heightfield;
 mod hit;
+mod instance;
+mod mat;
+mod node;
 mod plane;
+mod quad;
+mod quadric;
+mod random;
 mod renderable;
+mod roots;
+mod scene_file;
+mod sdf;
 mod sphere;
+mod stereo;
+mod torus;
 mod vec3;
 
 use crate::util::normalize;
-use crate::{renderer::Renderer, HEIGHT, WIDTH};
+use crate::renderer::{to_rgba8, Pass, Renderer, Stats, FPS};
+use crate::{HEIGHT, WIDTH};
+use csg::{Csg, Operation};
+use cuboid::Cuboid;
+use disk::Disk;
+use fingerprint::{Fingerprint, Fnv};
 use hit::Hit;
+use instance::Instance;
+use mat::Mat4;
+use node::{Node, Transform};
 use plane::Plane;
+use quad::Quad;
+use quadric::{Cone, Cylinder, Paraboloid};
+use random::Random;
 use renderable::Renderable;
-use sphere::{MovingSphere, Sphere};
-use vec3::Vec3;
+use animated::Animated;
+use sdf::{DistanceField, Sdf};
+use sphere::Sphere;
+use torus::Torus;
+pub use camera::{Camera, Fov, Lens, Projection};
+pub use camera_path::CameraPath;
+pub use heightfield::Heightfield;
+pub use scene_file::SceneFile;
+pub use stereo::{Layout, Stereo};
+pub use vec3::Vec3;
 
-struct Rgba {
-    r: u8,
-    g: u8,
-    b: u8,
-    a: u8,
-}
-
-impl Rgba {
-    const BLACK: Rgba = Rgba {
-        r: 0,
-        g: 0,
-        b: 0,
-        a: 0,
-    };
-}
-
-impl From<Rgb> for Rgba {
-    fn from(other: Rgb) -> Self {
-        Rgba {
-            r: other.r,
-            g: other.g,
-            b: other.b,
-            a: 0xFF,
-        }
-    }
-}
-
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 struct Rgb {
     r: u8,
     g: u8,
@@ -62,6 +77,57 @@ impl Rgb {
     };
 }
 
+/// A colour in floating point, 1.0 corresponds to 0xFF
+#[derive(Clone, Copy, Debug)]
+struct Color {
+    r: f64,
+    g: f64,
+    b: f64,
+}
+
+impl std::ops::Add for Color {
+    type Output = Color;
+    fn add(self, rhs: Color) -> Color {
+        Color {
+            r: self.r + rhs.r,
+            g: self.g + rhs.g,
+            b: self.b + rhs.b,
+        }
+    }
+}
+
+impl std::ops::Sub for Color {
+    type Output = Color;
+    fn sub(self, rhs: Color) -> Color {
+        Color {
+            r: self.r - rhs.r,
+            g: self.g - rhs.g,
+            b: self.b - rhs.b,
+        }
+    }
+}
+
+impl std::ops::Mul<f64> for Color {
+    type Output = Color;
+    fn mul(self, rhs: f64) -> Color {
+        Color {
+            r: self.r * rhs,
+            g: self.g * rhs,
+            b: self.b * rhs,
+        }
+    }
+}
+
+impl From<Rgb> for Color {
+    fn from(other: Rgb) -> Self {
+        Color {
+            r: other.r as f64 / 255.,
+            g: other.g as f64 / 255.,
+            b: other.b as f64 / 255.,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct Ray {
     p: Vec3,
@@ -75,25 +141,98 @@ impl Ray {
     };
 }
 
-trait Light {
+trait Light: std::fmt::Debug + Fingerprint {
     /// TODO How?
-    fn color(&self, l: &Vec3) -> Rgba;
+    fn color(&self, l: &Vec3) -> Color;
 }
 
+#[derive(Debug)]
 struct Scene {
     objects: Vec<Box<dyn Renderable>>,
+    /// Objects placed by a hierarchy of transforms, flattened after `objects` when rendering
+    graph: Node,
+    /// The ground, intersected after `objects`
+    floor: Box<dyn Renderable>,
     lights: Vec<Box<dyn Light>>,
     sunlight: Vec3,
+    /// Background colour where rays leave the scene
+    sky: Animated<Color>,
+}
+
+impl Fingerprint for Scene {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Scene {
+            objects,
+            graph,
+            floor,
+            lights,
+            sunlight,
+            sky,
+        } = self;
+        objects.fingerprint(h);
+        graph.fingerprint(h);
+        floor.fingerprint(h);
+        lights.fingerprint(h);
+        sunlight.fingerprint(h);
+        sky.fingerprint(h);
+    }
+}
+
+/// Settings that trade render time for image quality
+#[derive(Clone, Copy, Debug)]
+pub struct Quality {
+    pub width: u16,
+    pub height: u16,
+    /// Rays per pixel, spread evenly over the pixel
+    pub samples: u32,
+    /// Maximum number of reflections
+    pub depth: u32,
+}
+
+impl Quality {
+    pub const PREVIEW: Quality = Quality {
+        width: WIDTH,
+        height: HEIGHT,
+        samples: 1,
+        depth: 7,
+    };
+
+    pub const OFFLINE: Quality = Quality {
+        width: 2 * WIDTH,
+        height: 2 * HEIGHT,
+        samples: 9,
+        depth: 12,
+    };
+}
+
+impl Fingerprint for Quality {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Quality { width, height, samples, depth } = self;
+        width.fingerprint(h);
+        height.fingerprint(h);
+        samples.fingerprint(h);
+        depth.fingerprint(h);
+    }
 }
 
 pub struct Raytracer {
     scene: Scene,
-    pos: Vec3,
-    dir: Vec3,
-    near_plane: f64,
-    fov: f64,
+    camera: Camera,
+    /// How the camera moves while it follows the animation
+    pub camera_path: CameraPath,
+    pub quality: Quality,
+    /// Fraction of a frame the shutter stays open, 0 captures a single instant
+    pub shutter: f64,
+    /// Renders both eyes and combines them
+    pub stereo: Option<Stereo>,
+    /// Auxiliary buffers of the last rendered frame
+    passes: HashMap<Pass, Vec<f32>>,
+    stats: Stats,
 }
 
+/// Instants a frame is sampled at while the shutter is open
+const SHUTTER_STEPS: u32 = 16;
+
 const START_POS: Vec3 = Vec3 {
     x: 0.,
     y: 70.,
@@ -102,76 +241,111 @@ const START_POS: Vec3 = Vec3 {
 
 impl Raytracer {
     pub fn new() -> Raytracer {
+        // One sphere placed twice, squashed into ellipsoids resting on the floor
+        let pebble: Rc<dyn Renderable> = Rc::new(Sphere {
+            pos: Animated::Constant(Vec3::NULL),
+            r: Animated::Constant(1.),
+            color: Color::from(Rgb {
+                r: 0x8a,
+                g: 0x9a,
+                b: 0x5b,
+            })
+            .into(),
+            reflectivity: Animated::Constant(0.3),
+        });
+        let pebble_at = |x: f64, z: f64, angle: f64| Node {
+            transforms: vec![
+                Transform::Translate(Vec3 { x, y: -90., z }.into()),
+                Transform::Rotate(Vec3::UP, angle.into()),
+                Transform::Scale(Vec3 { x: 30., y: 10., z: 15. }.into()),
+            ],
+            objects: vec![pebble.clone()],
+            ..Default::default()
+        };
+        let moon = |y: f64, r: f64, color: Rgb, reflectivity: f64| Node {
+            transforms: vec![Transform::Translate(Vec3 { x: 0., y, z: 0. }.into())],
+            objects: vec![Rc::new(Sphere {
+                pos: Vec3::NULL.into(),
+                r: Animated::Constant(r),
+                color: Color::from(color).into(),
+                reflectivity: Animated::Constant(reflectivity),
+            })],
+            ..Default::default()
+        };
+        // Two moons on opposite sides, circling the central sphere together
+        let moons = Node {
+            transforms: vec![Transform::Rotate(
+                Vec3 { x: 0., y: 0., z: 1. },
+                Animated::formula("-2 * t").unwrap(),
+            )],
+            children: vec![
+                moon(
+                    60.,
+                    10.,
+                    Rgb {
+                        r: 0x18,
+                        g: 0x39,
+                        b: 0x3E,
+                    },
+                    0.2,
+                ),
+                moon(
+                    -60.,
+                    5.,
+                    Rgb {
+                        r: 0x4f,
+                        g: 0x2c,
+                        b: 0x1b,
+                    },
+                    0.5,
+                ),
+            ],
+            ..Default::default()
+        };
         Raytracer {
             scene: Scene {
+                graph: Node {
+                    children: vec![moons, pebble_at(-120., 120., 0.5), pebble_at(120., -120., -0.3)],
+                    ..Default::default()
+                },
                 objects: vec![
-                    Box::new(MovingSphere {
-                        pos: moon_path,
-                        r: 10.,
-                        color: Rgb {
-                            r: 0x18,
-                            g: 0x39,
-                            b: 0x3E,
-                        },
-                        reflectivity: 0.2,
-                    }),
-                    Box::new(MovingSphere {
-                        pos: |t| -moon_path(t),
-                        r: 5.,
-                        color: Rgb {
-                            r: 0x4f,
-                            g: 0x2c,
-                            b: 0x1b,
-                        },
-                        reflectivity: 0.5,
-                    }),
                     Box::new(Sphere {
                         pos: Vec3 {
                             x: 0.,
                             y: 0.,
                             z: 0.,
-                        },
-                        r: 30.,
-                        color: Rgb {
+                        }
+                        .into(),
+                        r: Animated::Constant(30.),
+                        color: Color::from(Rgb {
                             r: 0x4f,
                             g: 0x2c,
                             b: 0x1b,
-                        },
-                        reflectivity: 1.0,
+                        })
+                        .into(),
+                        reflectivity: Animated::Constant(1.0),
                     }),
                     // PLANES
-                    Box::new(Plane {
-                        pos: Vec3 {
-                            x: 0.,
-                            y: -100.,
-                            z: 0.,
-                        },
-                        n: Vec3 {
-                            x: 0.,
-                            y: 1.,
-                            z: 0.,
-                        },
-                        color: Rgb::WHITE,
-                        reflectivity: 0.7,
-                        checker: true,
-                    }),
                     Box::new(Plane {
                         pos: Vec3 {
                             x: 250.,
                             y: 0.,
                             z: 0.,
-                        },
+                        }
+                        .into(),
                         n: Vec3 {
                             x: -1.,
                             y: 0.,
                             z: 0.,
-                        },
-                        color: Rgb {
+                        }
+                        .into(),
+                        color: Color::from(Rgb {
                             r: 0xAA,
                             g: 0xAA,
                             b: 0xAA,
-                        },
-                        reflectivity: 1.,
+                        })
+                        .into(),
+                        reflectivity: Animated::Constant(1.),
                         checker: false,
                     }),
                     Box::new(Plane {
@@ -179,64 +353,440 @@ impl Raytracer {
                             x: 0.,
                             y: 0.,
                             z: 250.,
-                        },
+                        }
+                        .into(),
                         n: Vec3 {
                             x: 0.,
                             y: 0.,
                             z: -1.,
-                        },
-                        color: Rgb {
+                        }
+                        .into(),
+                        color: Color::from(Rgb {
                             r: 0xAA,
                             g: 0xAA,
                             b: 0xAA,
-                        },
-                        reflectivity: 1.,
+                        })
+                        .into(),
+                        reflectivity: Animated::Constant(1.),
                         checker: false,
                     }),
                 ],
+                floor: Box::new(Plane {
+                    pos: Vec3 {
+                        x: 0.,
+                        y: -100.,
+                        z: 0.,
+                    }
+                    .into(),
+                    n: Vec3 {
+                        x: 0.,
+                        y: 1.,
+                        z: 0.,
+                    }
+                    .into(),
+                    color: Color::from(Rgb::WHITE).into(),
+                    reflectivity: Animated::Constant(0.7),
+                    checker: true,
+                }),
                 lights: vec![],
                 sunlight: Vec3 {
                     x: 5.,
                     y: -3.,
                     z: 1.,
                 },
+                sky: Color::from(Rgb::SKY).into(),
+            },
+            camera: Camera {
+                scripted: true,
+                // Frames the scene like the earlier fixed frustum did
+                ..Camera::look_at(START_POS, Vec3::NULL, Vec3::UP, Fov::Horizontal(65.))
+            },
+            camera_path: CameraPath::orbit(),
+            quality: Quality::PREVIEW,
+            shutter: 0.,
+            stereo: None,
+            passes: HashMap::new(),
+            stats: Stats::default(),
+        }
+    }
+
+    /// Adds an example of every kind of object around the scene
+    pub fn showcase(&mut self) {
+        let objects: Vec<Box<dyn Renderable>> = vec![
+            // A crate turned with an instance, a pond and a panel
+            Box::new(Instance::new(
+                Rc::new(Cuboid {
+                    min: Vec3 { x: -15., y: -15., z: -15. }.into(),
+                    max: Vec3 { x: 15., y: 15., z: 15. }.into(),
+                    color: Color::from(Rgb {
+                        r: 0x8b,
+                        g: 0x5a,
+                        b: 0x2b,
+                    })
+                    .into(),
+                    reflectivity: Animated::Constant(0.1),
+                }),
+                Mat4::translate(Vec3 { x: -120., y: -85., z: -120. })
+                    * Mat4::rotate(Vec3 { x: 0., y: 1., z: 0. }, 0.6),
+            )
+            .unwrap()),
+            Box::new(Disk {
+                pos: Vec3 { x: 120., y: -99.5, z: 120. }.into(),
+                n: Vec3::UP.into(),
+                r: Animated::Constant(35.),
+                color: Color::from(Rgb {
+                    r: 0x2b,
+                    g: 0x4f,
+                    b: 0x6e,
+                })
+                .into(),
+                reflectivity: Animated::Constant(0.8),
+            }),
+            Box::new(Quad {
+                corner: Vec3 { x: -200., y: -100., z: -40. }.into(),
+                a: Vec3 { x: 0., y: 0., z: 80. }.into(),
+                b: Vec3 { x: 0., y: 70., z: 0. }.into(),
+                color: Color::from(Rgb {
+                    r: 0x6e,
+                    g: 0x2b,
+                    b: 0x3e,
+                })
+                .into(),
+                reflectivity: Animated::Constant(0.4),
+            }),
+            // A ring around the central sphere, a column, a cone and a dish
+            Box::new(Torus {
+                pos: Vec3::NULL.into(),
+                major: Animated::Constant(45.),
+                minor: Animated::Constant(3.),
+                color: Color::from(Rgb {
+                    r: 0xc9,
+                    g: 0xa2,
+                    b: 0x27,
+                })
+                .into(),
+                reflectivity: Animated::Constant(0.5),
+            }),
+            Box::new(Cylinder {
+                pos: Vec3 { x: 130., y: -100., z: 0. }.into(),
+                r: Animated::Constant(10.),
+                height: Animated::Constant(50.),
+                capped: true,
+                color: Color::from(Rgb {
+                    r: 0xAA,
+                    g: 0xAA,
+                    b: 0xAA,
+                })
+                .into(),
+                reflectivity: Animated::Constant(0.2),
+            }),
+            Box::new(Cone {
+                pos: Vec3 { x: 0., y: -100., z: 140. }.into(),
+                r: Animated::Constant(15.),
+                height: Animated::Constant(40.),
+                capped: true,
+                color: Color::from(Rgb {
+                    r: 0x3e,
+                    g: 0x6e,
+                    b: 0x2b,
+                })
+                .into(),
+                reflectivity: Animated::Constant(0.1),
+            }),
+            Box::new(Paraboloid {
+                pos: Vec3 { x: 0., y: -100., z: -140. }.into(),
+                r: Animated::Constant(20.),
+                height: Animated::Constant(15.),
+                capped: false,
+                color: Color::from(Rgb {
+                    r: 0xAA,
+                    g: 0xAA,
+                    b: 0xAA,
+                })
+                .into(),
+                reflectivity: Animated::Constant(0.9),
+            }),
+            // A sphere with a corner carved out
+            Box::new(Csg {
+                operation: Operation::Difference,
+                a: Box::new(Sphere {
+                    pos: Vec3 { x: -100., y: -75., z: 40. }.into(),
+                    r: Animated::Constant(25.),
+                    color: Color::from(Rgb {
+                        r: 0x18,
+                        g: 0x39,
+                        b: 0x3E,
+                    })
+                    .into(),
+                    reflectivity: Animated::Constant(0.3),
+                }),
+                b: Box::new(Cuboid {
+                    min: Vec3 { x: -100., y: -75., z: 40. }.into(),
+                    max: Vec3 { x: -70., y: -45., z: 70. }.into(),
+                    color: Color::from(Rgb {
+                        r: 0xc9,
+                        g: 0xa2,
+                        b: 0x27,
+                    })
+                    .into(),
+                    reflectivity: Animated::Constant(0.),
+                }),
+            }),
+            // A die rounded off where the cube pokes out of a sphere
+            Box::new(Csg {
+                operation: Operation::Intersection,
+                a: Box::new(Cuboid {
+                    min: Vec3 { x: 85., y: -100., z: -75. }.into(),
+                    max: Vec3 { x: 115., y: -70., z: -45. }.into(),
+                    color: Color::from(Rgb::WHITE).into(),
+                    reflectivity: Animated::Constant(0.1),
+                }),
+                b: Box::new(Sphere {
+                    pos: Vec3 { x: 100., y: -85., z: -60. }.into(),
+                    r: Animated::Constant(20.),
+                    color: Color::from(Rgb::WHITE).into(),
+                    reflectivity: Animated::Constant(0.1),
+                }),
+            }),
+            // A snowman, two spheres melted into one solid
+            Box::new(Csg {
+                operation: Operation::Union,
+                a: Box::new(Sphere {
+                    pos: Vec3 { x: -60., y: -88., z: -100. }.into(),
+                    r: Animated::Constant(12.),
+                    color: Color::from(Rgb::WHITE).into(),
+                    reflectivity: Animated::Constant(0.),
+                }),
+                b: Box::new(Sphere {
+                    pos: Vec3 { x: -60., y: -71., z: -100. }.into(),
+                    r: Animated::Constant(8.),
+                    color: Color::from(Rgb::WHITE).into(),
+                    reflectivity: Animated::Constant(0.),
+                }),
+            }),
+            // A twisted, dimpled column with a ball on top, arms and a ring at its foot
+            Box::new(DistanceField {
+                pos: Vec3 { x: 75., y: -75., z: 75. }.into(),
+                sdf: Sdf::SmoothSubtraction(
+                    Box::new(Sdf::SmoothUnion(
+                        Box::new(Sdf::SmoothUnion(
+                            Box::new(Sdf::Twist(
+                                0.05,
+                                Box::new(Sdf::RoundBox(Vec3 { x: 8., y: 20., z: 8. }, 2.)),
+                            )),
+                            Box::new(Sdf::Translate(Vec3 { x: 0., y: 24., z: 0. }, Box::new(Sdf::Sphere(9.)))),
+                            4.,
+                        )),
+                        Box::new(Sdf::SmoothUnion(
+                            Box::new(Sdf::Capsule(
+                                Vec3 { x: -18., y: 5., z: 0. },
+                                Vec3 { x: 18., y: 5., z: 0. },
+                                2.5,
+                            )),
+                            Box::new(Sdf::Translate(Vec3 { x: 0., y: -22., z: 0. }, Box::new(Sdf::Torus(13., 3.)))),
+                            2.,
+                        )),
+                        3.,
+                    )),
+                    Box::new(Sdf::Repeat(Vec3 { x: 6., y: 6., z: 6. }, Box::new(Sdf::Sphere(1.5)))),
+                    1.,
+                ),
+                bound: Some(40.),
+                color: Color::from(Rgb {
+                    r: 0x9a,
+                    g: 0x6e,
+                    b: 0xb0,
+                })
+                .into(),
+                reflectivity: Animated::Constant(0.2),
+            }),
+        ];
+        self.scene.objects.extend(objects);
+    }
+
+    /// Terrain from a heightmap, covering the floor up to the walls
+    pub fn terrain(path: &Path) -> Result<Heightfield, String> {
+        Heightfield::load(
+            path,
+            Vec3 {
+                x: -250.,
+                y: -100.,
+                z: -250.,
+            },
+            Vec3 {
+                x: 500.,
+                y: 50.,
+                z: 500.,
             },
-            pos: START_POS,
-            dir: (Vec3::NULL - START_POS).norm(),
-            near_plane: 1.,
-            fov: 60.,
+        )
+    }
+
+    /// Adds the spheres of a scene file and replaces the sky if it has one
+    pub fn add(&mut self, scene_file: &SceneFile) {
+        if let Some(sky) = &scene_file.sky {
+            self.scene.sky = sky.clone();
+        }
+        for sphere in &scene_file.spheres {
+            self.scene.objects.push(Box::new(sphere.clone()));
+        }
+    }
+
+    /// Replaces the checkered floor
+    pub fn set_terrain(&mut self, terrain: Heightfield) {
+        self.scene.floor = Box::new(terrain);
+    }
+
+    /// The camera at a point in time, which only changes if it follows the animation
+    fn camera_at(&self, t: f64) -> Camera {
+        let mut camera = self.camera;
+        if camera.scripted {
+            let shot = self.camera_path.at(t);
+            camera.pos = shot.pos;
+            camera.dir = (shot.target - shot.pos).norm();
+            if let Some(fov) = shot.fov {
+                camera.fov = Fov::Horizontal(fov);
+            }
+            if let Some(focus) = shot.focus {
+                camera.lens.focus = focus;
+            }
         }
+        camera
     }
 
-    /// Returns three Vec3s: bottom left corner, dx, and dy
-    fn frustum(&self) -> (Vec3, Vec3, Vec3) {
-        let left = self.dir.cross(Vec3::UP).norm();
-        let down = self.dir.cross(left).norm();
+    /// Renders the image of an eye `eye` to the right of the camera
+    fn render_view(&mut self, t: f64, camera: Camera, width: u16, height: u16, eye: f64) -> Vec<f32> {
+        let Quality { samples, depth, .. } = self.quality;
+        let size = 3 * width as usize * height as usize;
+        let mut pixels = vec![0.; size];
+        for pass in Pass::AUXILIARY.iter() {
+            self.passes.insert(*pass, vec![0.; size]);
+        }
+        // Samples are placed on a grid around the pixel position
+        let grid = (samples as f64).sqrt().ceil() as u32;
+        let offset = |i: u32| (i as f64 + 0.5) / grid as f64 - 0.5;
+        self.stats.samples = grid * grid;
+        let view = self.view(camera, eye);
+        // While the shutter is open samples see one of a few instants, each flattened once
+        let instants: Vec<(f64, Camera, Vec<Instance>)> = if self.shutter > 0. {
+            (0..SHUTTER_STEPS)
+                .map(|i| {
+                    let time = t + (i as f64 + 0.5) / SHUTTER_STEPS as f64 * self.shutter / FPS;
+                    (time, self.view(self.camera_at(time), eye), self.scene.graph.flatten(time))
+                })
+                .collect()
+        } else {
+            vec![(t, view, self.scene.graph.flatten(t))]
+        };
 
-        // Calculate bottom left corner
-        let center = self.near_plane * self.dir + self.pos;
-        // x and y unit distance
-        let x0 = 2. * self.near_plane * self.fov.tan();
-        let ratio = (WIDTH as f64) / (HEIGHT as f64);
-        let y0 = x0 / ratio;
-        let topleft: Vec3 = center + left * x0 - down * y0;
+        for y in 0..height {
+            for x in 0..width {
+                let mut random = Random::new((y as u64) << 32 | x as u64);
+                let mut color = Color { r: 0., g: 0., b: 0. };
+                // The first sample fills the auxiliary passes
+                let mut first = None;
+                let mut tests = 0;
+                for sample in 0..grid * grid {
+                    let sx = x as f64 + 0.5 + offset(sample % grid);
+                    let sy = y as f64 + 0.5 + offset(sample / grid);
+                    let (time, camera, instances) = if self.shutter > 0. {
+                        &instants[(random.next_f64() * SHUTTER_STEPS as f64) as usize]
+                    } else {
+                        &instants[0]
+                    };
+                    let ray = camera.ray(sx, sy, width, height, &mut random);
+                    let mut trace = Trace::default();
+                    let c = match intersect(&ray, &self.scene, instances, depth, *time, &mut trace) {
+                        Some(x) => x,
+                        None => self.scene.sky.at(*time),
+                    };
+                    color.r += c.r;
+                    color.g += c.g;
+                    color.b += c.b;
+                    tests += trace.tests;
+                    self.stats.rays += 1 + trace.bounces as u64;
+                    first.get_or_insert(trace);
+                }
+                let trace = first.unwrap();
+                let n = (grid * grid) as f64;
+                let i = (((y as usize * width as usize) + x as usize) * 3) as usize;
+                pixels[i] = (color.r / n) as f32;
+                pixels[i + 1] = (color.g / n) as f32;
+                pixels[i + 2] = (color.b / n) as f32;
 
-        let width = x0 * -2.;
-        let height = y0 * -2.;
+                if let Some(Primary {
+                    object,
+                    position,
+                    normal,
+                    albedo,
+                    uv: (u, v),
+                }) = trace.primary
+                {
+                    let depth = (position - view.pos).dot(view.dir);
+                    self.store(Pass::Depth, i, Vec3 { x: depth, y: depth, z: depth });
+                    self.store(Pass::Normal, i, normal);
+                    self.store(Pass::Albedo, i, Vec3 { x: albedo.r, y: albedo.g, z: albedo.b });
+                    // Object ids start at 1, 0 is the background
+                    let id = (object + 1) as f64;
+                    self.store(Pass::ObjectId, i, Vec3 { x: id, y: id, z: id });
+                    self.store(Pass::Position, i, position);
+                    self.store(Pass::Uv, i, Vec3 { x: u.rem_euclid(1.), y: v.rem_euclid(1.), z: 0. });
+                }
+                let bounces = trace.bounces as f64;
+                self.store(Pass::Bounces, i, Vec3 { x: bounces, y: bounces, z: bounces });
+                let tests = tests as f64;
+                self.store(Pass::Tests, i, Vec3 { x: tests, y: tests, z: tests });
+            }
+        }
+
+        pixels
+    }
 
-        let dx = (width / WIDTH as f64) * left;
-        let dy = -(height / HEIGHT as f64) * down;
+    /// The camera of an eye, which is the camera itself without stereo
+    fn view(&self, camera: Camera, eye: f64) -> Camera {
+        match self.stereo {
+            Some(stereo) => camera.eye(eye, stereo.convergence),
+            None => camera,
+        }
+    }
 
-        (topleft, dx, dy)
+    fn store(&mut self, pass: Pass, i: usize, v: Vec3) {
+        let buffer = self.passes.get_mut(&pass).unwrap();
+        buffer[i] = v.x as f32;
+        buffer[i + 1] = v.y as f32;
+        buffer[i + 2] = v.z as f32;
     }
 }
 
-fn intersect(ray: &Ray, scene: &Scene, depth: u32, t: f64) -> Option<Rgb> {
-    let mut hits: Vec<Hit> = (&scene.objects)
-        .into_iter()
-        .filter_map(|obj| obj.intersects(&ray, t))
+/// The first surface a ray hit
+struct Primary {
+    /// Index of the object
+    object: usize,
+    position: Vec3,
+    normal: Vec3,
+    albedo: Color,
+    /// Texture coordinates
+    uv: (f64, f64),
+}
+
+/// What a ray ran into besides its colour
+#[derive(Default)]
+struct Trace {
+    primary: Option<Primary>,
+    /// Number of reflections that were followed
+    bounces: u32,
+    /// Number of ray-object intersection tests
+    tests: u32,
+}
+
+fn intersect(ray: &Ray, scene: &Scene, instances: &[Instance], depth: u32, t: f64, trace: &mut Trace) -> Option<Color> {
+    trace.tests += (scene.objects.len() + 1 + instances.len()) as u32;
+    let mut hits: Vec<(usize, Hit)> = (scene.objects.iter().map(|obj| obj.as_ref()))
+        .chain(std::iter::once(scene.floor.as_ref()))
+        .chain(instances.iter().map(|instance| instance as &dyn Renderable))
+        .enumerate()
+        .filter_map(|(i, obj)| obj.intersects(&ray, t).map(|hit| (i, hit)))
         .collect();
-    hits.sort_by(|a, b| {
+    hits.sort_by(|(_, a), (_, b)| {
         ((a.reflection.p - ray.p)
             .mag()
             .partial_cmp(&(b.reflection.p - ray.p).mag()))
@@ -244,16 +794,31 @@ fn intersect(ray: &Ray, scene: &Scene, depth: u32, t: f64) -> Option<Rgb> {
     });
     match hits.first() {
         None => None,
-        Some(Hit {
-            reflection,
-            color,
-            reflectivity,
-        }) => {
+        Some((
+            object,
+            Hit {
+                reflection,
+                color,
+                reflectivity,
+                normal,
+                uv,
+            },
+        )) => {
+            if trace.primary.is_none() {
+                trace.primary = Some(Primary {
+                    object: *object,
+                    position: reflection.p,
+                    normal: *normal,
+                    albedo: *color,
+                    uv: *uv,
+                });
+            }
             if depth > 0 && *reflectivity > 0. {
-                let reflected = intersect(&reflection, scene, depth - 1, t);
+                trace.bounces += 1;
+                let reflected = intersect(&reflection, scene, instances, depth - 1, t, trace);
                 return match reflected {
                     Some(reflected_color) => mix_reflection(*color, reflected_color, *reflectivity),
-                    None => mix_reflection(*color, Rgb::SKY, *reflectivity), // Background color
+                    None => mix_reflection(*color, scene.sky.at(t), *reflectivity),
                 };
             }
             Some(*color)
@@ -267,64 +832,120 @@ fn interpolate(a: f64, b: f64, t: f64) -> f64 {
     a + delta
 }
 
-fn mix_reflection(color: Rgb, reflected_color: Rgb, reflectivity: f64) -> Option<Rgb> {
-    let r = interpolate(color.r.into(), reflected_color.r.into(), reflectivity);
-    let g = interpolate(color.g.into(), reflected_color.g.into(), reflectivity);
-    let b = interpolate(color.b.into(), reflected_color.b.into(), reflectivity);
-    // FIXME Handle overflow
-    Some(Rgb {
-        r: r.round() as u8,
-        g: g.round() as u8,
-        b: b.round() as u8,
+fn mix_reflection(color: Color, reflected_color: Color, reflectivity: f64) -> Option<Color> {
+    Some(Color {
+        r: interpolate(color.r, reflected_color.r, reflectivity),
+        g: interpolate(color.g, reflected_color.g, reflectivity),
+        b: interpolate(color.b, reflected_color.b, reflectivity),
     })
 }
 
-fn path(t: f64) -> Vec3 {
-    let y = t.cos() * 40.;
-    let r = 200.;
-    let x = r * t.cos();
-    let z = r * t.sin();
-    Vec3 { x, y, z }
-}
-
-fn moon_path(t: f64) -> Vec3 {
-    let z = 0.;
-    let r = 60.;
-    let x = r * (2.*t).sin();
-    let y = r * (2.*t).cos();
-    Vec3 { x, y, z }
-}
-
 impl Renderer for Raytracer {
     fn render(&mut self, t: f64) -> (Vec<u8>, u16, u16) {
-        // Move camera around
-        self.pos = path(t);
-        self.dir = (-self.pos).norm();
+        let (pixels, width, height) = self.render_hdr(t);
+        (to_rgba8(&pixels), width, height)
+    }
 
-        let (topleft, dx, dy) = self.frustum();
+    fn render_hdr(&mut self, t: f64) -> (Vec<f32>, u16, u16) {
+        // Move camera around
+        let camera = self.camera_at(t);
+        if camera.scripted {
+            // Steering by hand takes over where the path left the camera
+            self.camera.pos = camera.pos;
+            self.camera.dir = camera.dir;
+        }
 
-        let mut pixels = vec![0x00; 4 * WIDTH as usize * HEIGHT as usize];
-        let depth = 7;
+        let Quality { width, height, .. } = self.quality;
+        // Panoramas keep the width and get the height they need
+        let height = match camera.projection.aspect() {
+            Some(aspect) => (width as f64 / aspect).round() as u16,
+            None => height,
+        };
+        self.stats = Stats::default();
 
-        for y in 0..HEIGHT {
-            for x in 0..WIDTH {
-                let ray = Ray {
-                    p: self.pos,
-                    q: topleft + dx * (x as f64) + dy * (y as f64),
-                };
-                let color = match intersect(&ray, &self.scene, depth, t) {
-                    Some(x) => Rgba::from(x),
-                    None => Rgba::from(Rgb::SKY), // Background colour (todo make a constant)
-                };
-                let i = (((y as usize * WIDTH as usize) + x as usize) * 4) as usize;
-                pixels[i] = color.r;
-                pixels[i + 1] = color.g;
-                pixels[i + 2] = color.b;
-                pixels[i + 3] = color.a;
+        let stereo = match self.stereo {
+            Some(stereo) => stereo,
+            None => {
+                let pixels = self.render_view(t, camera, width, height, 0.);
+                debug!("Pos: {:?} Dir: {:?}", camera.pos, camera.dir);
+                return (pixels, width, height);
             }
+        };
+        let left = self.render_view(t, camera, width, height, -stereo.ipd / 2.);
+        let left_passes = std::mem::take(&mut self.passes);
+        let right = self.render_view(t, camera, width, height, stereo.ipd / 2.);
+        for (pass, buffer) in left_passes {
+            // Colours of auxiliary passes don't mix, so anaglyphs only show the left eye
+            let composed = match stereo.layout {
+                Layout::Anaglyph => buffer,
+                _ => stereo.compose(&buffer, &self.passes[&pass], width, height).0,
+            };
+            self.passes.insert(pass, composed);
+        }
+        debug!("Pos: {:?} Dir: {:?}", camera.pos, camera.dir);
+        stereo.compose(&left, &right, width, height)
+    }
+
+    fn render_offline(&mut self, t: f64) -> (Vec<u8>, u16, u16) {
+        let preview = self.quality;
+        self.quality = Quality::OFFLINE;
+        let r = self.render(t);
+        self.quality = preview;
+        r
+    }
+
+    fn pass(&self, pass: Pass) -> Option<Vec<f32>> {
+        self.passes.get(&pass).cloned()
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+
+    fn scene_hash(&self) -> u64 {
+        let Raytracer {
+            scene,
+            camera,
+            camera_path,
+            quality,
+            shutter,
+            stereo,
+            passes: _,
+            stats: _,
+        } = self;
+        let mut h = Fnv::new();
+        scene.fingerprint(&mut h);
+        // The path moves a scripted camera, so only where a free one stands counts
+        let Camera {
+            pos,
+            dir,
+            up,
+            fov,
+            projection,
+            roll,
+            shift,
+            lens,
+            scripted,
+        } = camera;
+        scripted.fingerprint(&mut h);
+        if !scripted {
+            pos.fingerprint(&mut h);
+            dir.fingerprint(&mut h);
         }
+        up.fingerprint(&mut h);
+        fov.fingerprint(&mut h);
+        projection.fingerprint(&mut h);
+        roll.fingerprint(&mut h);
+        shift.fingerprint(&mut h);
+        lens.fingerprint(&mut h);
+        camera_path.fingerprint(&mut h);
+        quality.fingerprint(&mut h);
+        shutter.fingerprint(&mut h);
+        stereo.fingerprint(&mut h);
+        h.finish()
+    }
 
-        println!("Pos: {:?} Dir: {:?}", self.pos, self.dir);
-        (pixels, WIDTH, HEIGHT)
+    fn stats(&self) -> Stats {
+        self.stats
     }
 }
@@ -0,0 +1,368 @@
+use std::ops::{Add, Mul, Sub};
+
+use super::expression::{Formula, ParseError};
+use super::fingerprint::{Fingerprint, Fnv};
+use super::vec3::Vec3;
+use super::Color;
+
+/// Values that can be mixed, which is all a spline needs
+pub trait Interpolate: Copy + Add<Output = Self> + Sub<Output = Self> + Mul<f64, Output = Self> {}
+
+impl<T> Interpolate for T where T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T> {}
+
+/// Values made of a fixed number of numbers, so formulas can compute them
+pub trait Components: Sized {
+    const COUNT: usize;
+    fn from_components(values: &[f64]) -> Self;
+}
+
+impl Components for f64 {
+    const COUNT: usize = 1;
+    fn from_components(values: &[f64]) -> f64 {
+        values[0]
+    }
+}
+
+impl Components for Vec3 {
+    const COUNT: usize = 3;
+    fn from_components(values: &[f64]) -> Vec3 {
+        Vec3 {
+            x: values[0],
+            y: values[1],
+            z: values[2],
+        }
+    }
+}
+
+impl Components for Color {
+    const COUNT: usize = 3;
+    fn from_components(values: &[f64]) -> Color {
+        Color {
+            r: values[0],
+            g: values[1],
+            b: values[2],
+        }
+    }
+}
+
+/// How the time between two keyframes is spent
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Easing {
+    Linear,
+    /// Starts slow
+    In,
+    /// Ends slow
+    Out,
+    /// Starts and ends slow
+    InOut,
+}
+
+impl Easing {
+    pub fn parse(name: &str) -> Option<Easing> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "in" => Some(Easing::In),
+            "out" => Some(Easing::Out),
+            "in-out" => Some(Easing::InOut),
+            _ => None,
+        }
+    }
+
+    /// Maps 0..1 to 0..1
+    pub fn apply(&self, s: f64) -> f64 {
+        match self {
+            Easing::Linear => s,
+            Easing::In => s * s,
+            Easing::Out => 1. - (1. - s) * (1. - s),
+            Easing::InOut => s * s * (3. - 2. * s),
+        }
+    }
+}
+
+/// How values between keyframes are found
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    /// Holds the value of the last key
+    Step,
+    Linear,
+    /// Catmull-Rom spline through all keys
+    Spline,
+    /// Straight from key to key, but eased in and out of them
+    Ease(Easing),
+}
+
+impl Interpolation {
+    pub fn parse(name: &str) -> Option<Interpolation> {
+        match name {
+            "step" => Some(Interpolation::Step),
+            "linear" => Some(Interpolation::Linear),
+            "spline" => Some(Interpolation::Spline),
+            _ => Easing::parse(name.strip_prefix("ease-")?).map(Interpolation::Ease),
+        }
+    }
+}
+
+/// Values at points in time, made with `Keyframes::new`
+#[derive(Clone, Debug)]
+pub struct Keyframes<T> {
+    /// Times and values, strictly ascending by time
+    keys: Vec<(f64, T)>,
+    pub interpolation: Interpolation,
+    /// Repeats the keys, a looping animation ends with its first value
+    pub looping: bool,
+}
+
+/// A value that can change over time
+#[derive(Clone, Debug)]
+pub enum Animated<T> {
+    Constant(T),
+    Keyframes(Keyframes<T>),
+    /// Computed from the time, with one expression per component
+    Formula(Formula),
+}
+
+impl<T> From<T> for Animated<T> {
+    fn from(value: T) -> Animated<T> {
+        Animated::Constant(value)
+    }
+}
+
+impl<T: Interpolate + Components> Animated<T> {
+    /// Parses a formula, which has to have as many components as the value
+    pub fn formula(source: &str) -> Result<Animated<T>, ParseError> {
+        let formula = Formula::parse(source)?;
+        formula.expect_len(T::COUNT)?;
+        Ok(Animated::Formula(formula))
+    }
+
+    pub fn at(&self, t: f64) -> T {
+        match self {
+            Animated::Constant(value) => *value,
+            Animated::Keyframes(keyframes) => keyframes.at(t),
+            Animated::Formula(formula) => T::from_components(&formula.eval(t)),
+        }
+    }
+}
+
+impl<T: Interpolate> Keyframes<T> {
+    /// Needs at least one key, with times that are numbers and strictly ascending
+    pub fn new(keys: Vec<(f64, T)>, interpolation: Interpolation, looping: bool) -> Result<Keyframes<T>, String> {
+        if keys.is_empty() {
+            return Err("keyframes need at least one key".to_string());
+        }
+        if let Some(i) = keys.iter().position(|key| !key.0.is_finite()) {
+            return Err(format!("key {} has no time", i + 1));
+        }
+        if let Some(i) = keys.windows(2).position(|pair| pair[0].0 >= pair[1].0) {
+            return Err(format!("key {} has to come after key {}", i + 2, i + 1));
+        }
+        Ok(Keyframes {
+            keys,
+            interpolation,
+            looping,
+        })
+    }
+
+    pub fn at(&self, t: f64) -> T {
+        let keys = &self.keys;
+        let n = keys.len();
+        if n == 1 {
+            return keys[0].1;
+        }
+        let (start, end) = (keys[0].0, keys[n - 1].0);
+        let t = if self.looping {
+            start + (t - start).rem_euclid(end - start)
+        } else {
+            t.clamp(start, end)
+        };
+
+        // The segment between key i and i + 1
+        let i = keys[..n - 1].iter().rposition(|key| key.0 <= t).unwrap_or(0);
+        let s = (t - keys[i].0) / (keys[i + 1].0 - keys[i].0);
+        let (a, b) = (keys[i].1, keys[i + 1].1);
+        match self.interpolation {
+            Interpolation::Step => a,
+            Interpolation::Linear => a + (b - a) * s,
+            Interpolation::Ease(easing) => a + (b - a) * easing.apply(s),
+            Interpolation::Spline => {
+                let [before, after] = neighbours(i, n, self.looping);
+                catmull_rom([keys[before].1, a, b, keys[after].1], s)
+            }
+        }
+    }
+}
+
+impl Fingerprint for Easing {
+    fn fingerprint(&self, h: &mut Fnv) {
+        h.tag(match self {
+            Easing::Linear => "linear",
+            Easing::In => "in",
+            Easing::Out => "out",
+            Easing::InOut => "in-out",
+        });
+    }
+}
+
+impl Fingerprint for Interpolation {
+    fn fingerprint(&self, h: &mut Fnv) {
+        match self {
+            Interpolation::Step => h.tag("step"),
+            Interpolation::Linear => h.tag("linear"),
+            Interpolation::Spline => h.tag("spline"),
+            Interpolation::Ease(easing) => {
+                h.tag("ease");
+                easing.fingerprint(h);
+            }
+        }
+    }
+}
+
+impl<T: Fingerprint> Fingerprint for Animated<T> {
+    fn fingerprint(&self, h: &mut Fnv) {
+        match self {
+            Animated::Constant(value) => {
+                h.tag("constant");
+                value.fingerprint(h);
+            }
+            Animated::Keyframes(Keyframes {
+                keys,
+                interpolation,
+                looping,
+            }) => {
+                h.tag("keyframes");
+                keys.fingerprint(h);
+                interpolation.fingerprint(h);
+                looping.fingerprint(h);
+            }
+            Animated::Formula(formula) => {
+                h.tag("formula");
+                formula.fingerprint(h);
+            }
+        }
+    }
+}
+
+/// Keys before and after the segment from key i to i + 1
+///
+/// Ends repeat the outer keys, loops continue on the other side, skipping the
+/// key that is repeated at the end.
+pub fn neighbours(i: usize, n: usize, looping: bool) -> [usize; 2] {
+    let before = match i {
+        0 if looping => n - 2,
+        0 => 0,
+        _ => i - 1,
+    };
+    let after = match i + 2 {
+        a if a < n => a,
+        _ if looping => 1,
+        _ => n - 1,
+    };
+    [before, after]
+}
+
+/// Cubic Bézier curve from the first to the last point
+pub fn bezier<T: Interpolate>(p: [T; 4], s: f64) -> T {
+    let r = 1. - s;
+    p[0] * (r * r * r) + p[1] * (3. * r * r * s) + p[2] * (3. * r * s * s) + p[3] * (s * s * s)
+}
+
+/// Uniform Catmull-Rom spline from the second to the third point
+pub fn catmull_rom<T: Interpolate>(p: [T; 4], s: f64) -> T {
+    // The same curve as a Bézier with handles a sixth of the distance between the neighbours away
+    bezier([p[1], p[1] + (p[2] - p[0]) * (1. / 6.), p[2] - (p[3] - p[1]) * (1. / 6.), p[2]], s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn keyframes(interpolation: Interpolation, looping: bool) -> Keyframes<f64> {
+        Keyframes::new(vec![(0., 0.), (1., 10.), (3., 30.), (4., 0.)], interpolation, looping).unwrap()
+    }
+
+    #[test]
+    fn validates_keys() {
+        assert!(Keyframes::<f64>::new(vec![], Interpolation::Linear, false).is_err());
+        assert!(Keyframes::new(vec![(1., 0.), (1., 1.)], Interpolation::Linear, true).is_err());
+        assert!(Keyframes::new(vec![(2., 0.), (1., 1.)], Interpolation::Linear, false).is_err());
+        assert!(Keyframes::new(vec![(f64::NAN, 0.)], Interpolation::Linear, false).is_err());
+        // A single key holds its value, looping or not
+        let single = Keyframes::new(vec![(1., 5.)], Interpolation::Spline, true).unwrap();
+        assert!(close(single.at(-3.), 5.) && close(single.at(7.), 5.));
+    }
+
+    #[test]
+    fn step() {
+        let k = keyframes(Interpolation::Step, false);
+        assert!(close(k.at(0.5), 0.) && close(k.at(1.), 10.) && close(k.at(2.9), 10.));
+    }
+
+    #[test]
+    fn linear() {
+        let k = keyframes(Interpolation::Linear, false);
+        assert!(close(k.at(0.5), 5.));
+        assert!(close(k.at(2.), 20.));
+        assert!(close(k.at(3.5), 15.));
+        // Holds the ends outside of the keys
+        assert!(close(k.at(-1.), 0.) && close(k.at(9.), 0.));
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_keys() {
+        let k = keyframes(Interpolation::Spline, false);
+        for (t, v) in [(0., 0.), (1., 10.), (3., 30.), (4., 0.)] {
+            assert!(close(k.at(t), v));
+        }
+        // Points on a line stay on it
+        assert!(close(catmull_rom([0., 1., 2., 3.], 0.25), 1.25));
+        // Overshoots towards the trend of the neighbours
+        assert!(catmull_rom([0., 0., 1., 5.], 0.5) < 0.5);
+    }
+
+    #[test]
+    fn bezier_curve() {
+        assert!(close(bezier([0., 0., 0., 1.], 0.), 0.));
+        assert!(close(bezier([0., 0., 0., 1.], 1.), 1.));
+        assert!(close(bezier([0., 1., 2., 3.], 0.5), 1.5));
+        assert!(close(bezier([0., 4., 4., 0.], 0.5), 3.));
+    }
+
+    #[test]
+    fn easing() {
+        for easing in [Easing::Linear, Easing::In, Easing::Out, Easing::InOut] {
+            assert!(close(easing.apply(0.), 0.) && close(easing.apply(1.), 1.));
+        }
+        assert!(close(Easing::In.apply(0.5), 0.25));
+        assert!(close(Easing::Out.apply(0.5), 0.75));
+        assert!(close(Easing::InOut.apply(0.5), 0.5));
+        assert!(Easing::InOut.apply(0.1) < 0.1);
+        let k = keyframes(Interpolation::Ease(Easing::In), false);
+        assert!(close(k.at(0.5), 2.5));
+    }
+
+    #[test]
+    fn looping() {
+        let k = keyframes(Interpolation::Linear, true);
+        assert!(close(k.at(4.5), 5.));
+        assert!(close(k.at(-0.5), k.at(3.5)));
+        assert!(close(k.at(10.), 20.));
+        // Loops continue the spline smoothly over the seam
+        let k = keyframes(Interpolation::Spline, true);
+        assert!(close(k.at(4.25), k.at(0.25)));
+        let slope = |t: f64| (k.at(t + 1e-4) - k.at(t - 1e-4)) / 2e-4;
+        assert!((slope(4.) - slope(0.)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn formulas() {
+        let position = Animated::<Vec3>::formula("t, 2 * t, 3").unwrap();
+        let p = position.at(2.);
+        assert!(close(p.x, 2.) && close(p.y, 4.) && close(p.z, 3.));
+        assert_eq!(Animated::<f64>::formula("t, t").unwrap_err().column, 4);
+        assert_eq!(Animated::<Vec3>::formula("t, t").unwrap_err().column, 5);
+    }
+}
@@ -0,0 +1,430 @@
+use std::f64::consts::PI;
+
+use super::fingerprint::{Fingerprint, Fnv};
+use super::random::Random;
+use super::vec3::Vec3;
+use super::Ray;
+
+/// Field of view in degrees, across the image width or height
+///
+/// The other direction follows from the aspect ratio of the output.
+#[derive(Clone, Copy, Debug)]
+pub enum Fov {
+    Horizontal(f64),
+    Vertical(f64),
+}
+
+/// How directions around the camera are laid out in the image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    Perspective,
+    /// Parallel rays, covering what the perspective view shows at the focus distance
+    Orthographic,
+    /// Equidistant fisheye, the angle from the view direction grows linearly with the distance from the centre
+    Fisheye,
+    /// Full 360° by 180° panorama, longitude across and latitude down the image
+    Equirectangular,
+    /// Six 90° views in two rows, right, left and up above down, front and back
+    Cubemap,
+}
+
+impl Projection {
+    pub fn parse(name: &str) -> Option<Projection> {
+        match name {
+            "perspective" => Some(Projection::Perspective),
+            "orthographic" => Some(Projection::Orthographic),
+            "fisheye" => Some(Projection::Fisheye),
+            "equirectangular" => Some(Projection::Equirectangular),
+            "cubemap" => Some(Projection::Cubemap),
+            _ => None,
+        }
+    }
+
+    /// Width by height the image has to have, if the projection needs a specific one
+    pub fn aspect(&self) -> Option<f64> {
+        match self {
+            Projection::Equirectangular => Some(2.),
+            Projection::Cubemap => Some(1.5),
+            _ => None,
+        }
+    }
+}
+
+/// A thin lens, which keeps only things at the focus distance sharp
+#[derive(Clone, Copy, Debug)]
+pub struct Lens {
+    /// Radius of the lens, 0 is a pinhole with everything in focus
+    pub aperture: f64,
+    /// Distance along the view direction that is in focus
+    pub focus: f64,
+    /// Number of aperture blades, which shape the blur of out of focus highlights, 0 is round
+    pub blades: u32,
+}
+
+impl Lens {
+    pub const PINHOLE: Lens = Lens {
+        aperture: 0.,
+        focus: 1.,
+        blades: 0,
+    };
+}
+
+impl Fingerprint for Fov {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let (tag, degrees) = match self {
+            Fov::Horizontal(degrees) => ("horizontal", degrees),
+            Fov::Vertical(degrees) => ("vertical", degrees),
+        };
+        h.tag(tag);
+        degrees.fingerprint(h);
+    }
+}
+
+impl Fingerprint for Projection {
+    fn fingerprint(&self, h: &mut Fnv) {
+        h.tag(match self {
+            Projection::Perspective => "perspective",
+            Projection::Orthographic => "orthographic",
+            Projection::Fisheye => "fisheye",
+            Projection::Equirectangular => "equirectangular",
+            Projection::Cubemap => "cubemap",
+        });
+    }
+}
+
+impl Fingerprint for Lens {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Lens { aperture, focus, blades } = self;
+        aperture.fingerprint(h);
+        focus.fingerprint(h);
+        blades.fingerprint(h);
+    }
+}
+
+/// A camera, perspective unless told otherwise
+///
+/// The world is left-handed: with `up` pointing along y and looking along z,
+/// x is to the right.
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    pub pos: Vec3,
+    /// Unit vector the camera looks along
+    pub dir: Vec3,
+    /// Which way is up in the image, doesn't have to be orthogonal to `dir`
+    pub up: Vec3,
+    pub fov: Fov,
+    pub projection: Projection,
+    /// Degrees the camera is turned clockwise around `dir`, which turns the picture the other way
+    pub roll: f64,
+    /// Moves the image centre to the right, in units of the image plane at distance 1
+    pub shift: f64,
+    /// Depth of field, only used by the perspective projection
+    pub lens: Lens,
+    /// Whether the camera follows the animation or is steered by hand
+    pub scripted: bool,
+}
+
+impl Camera {
+    pub fn look_at(from: Vec3, at: Vec3, up: Vec3, fov: Fov) -> Camera {
+        Camera {
+            pos: from,
+            dir: (at - from).norm(),
+            up,
+            fov,
+            projection: Projection::Perspective,
+            roll: 0.,
+            shift: 0.,
+            lens: Lens::PINHOLE,
+            scripted: false,
+        }
+    }
+
+    /// The camera of an eye `offset` to the right, looking at the same point at the convergence distance
+    ///
+    /// The image is shifted instead of turning the camera, which keeps both
+    /// images free of vertical parallax.
+    pub fn eye(&self, offset: f64, convergence: f64) -> Camera {
+        let (right, _) = self.basis();
+        Camera {
+            pos: self.pos + right * offset,
+            shift: self.shift - offset / convergence,
+            ..*self
+        }
+    }
+
+    /// Unit vectors pointing right and up in the image
+    ///
+    /// If `up` is parallel to `dir`, another axis is used so looking straight
+    /// up or down still works.
+    pub fn basis(&self) -> (Vec3, Vec3) {
+        let mut up = self.up;
+        if up.cross(self.dir).mag() < 1e-9 * up.mag() {
+            up = if self.dir.x.abs() < 0.9 {
+                Vec3 { x: 1., y: 0., z: 0. }
+            } else {
+                Vec3 { x: 0., y: 0., z: 1. }
+            };
+        }
+        let right = up.cross(self.dir).norm();
+        let up = self.dir.cross(right).norm();
+        let roll = -self.roll.to_radians();
+        (right.rotate(self.dir, roll), up.rotate(self.dir, roll))
+    }
+
+    /// Half the width and height of the image plane at distance 1
+    fn extent(&self, width: u16, height: u16) -> (f64, f64) {
+        let aspect = width as f64 / height as f64;
+        match self.fov {
+            Fov::Horizontal(degrees) => {
+                let x = (degrees.to_radians() / 2.).tan();
+                (x, x / aspect)
+            }
+            Fov::Vertical(degrees) => {
+                let y = (degrees.to_radians() / 2.).tan();
+                (y * aspect, y)
+            }
+        }
+    }
+
+    /// The ray through a point of an image with the given size
+    ///
+    /// `x` and `y` are in pixels from the top left corner, so the centre of
+    /// the top left pixel is at 0.5, 0.5.
+    pub(super) fn ray(&self, x: f64, y: f64, width: u16, height: u16, random: &mut Random) -> Ray {
+        let (right, up) = self.basis();
+        // -1 to 1 from the left to the right and from the bottom to the top
+        let u = 2. * x / width as f64 - 1.;
+        let v = 1. - 2. * y / height as f64;
+        let towards = |dir: Vec3| Ray {
+            p: self.pos,
+            q: self.pos + dir,
+        };
+        match self.projection {
+            Projection::Perspective => {
+                let (w, h) = self.extent(width, height);
+                let dir = self.dir + right * (u * w + self.shift) + up * (v * h);
+                self.through_lens(dir, right, up, random)
+            }
+            Projection::Orthographic => {
+                let (w, h) = self.extent(width, height);
+                let p = self.pos + (right * (u * w) + up * (v * h)) * self.lens.focus;
+                Ray { p, q: p + self.dir }
+            }
+            Projection::Fisheye => {
+                // Half the field of view across and down the image, which can be more than 90°
+                let aspect = width as f64 / height as f64;
+                let (w, h) = match self.fov {
+                    Fov::Horizontal(degrees) => (degrees.to_radians() / 2., degrees.to_radians() / 2. / aspect),
+                    Fov::Vertical(degrees) => (degrees.to_radians() / 2. * aspect, degrees.to_radians() / 2.),
+                };
+                let (a, b) = (u * w, v * h);
+                let theta = a.hypot(b);
+                if theta < 1e-12 {
+                    return towards(self.dir);
+                }
+                towards(self.dir * theta.cos() + (right * a + up * b) * (theta.sin() / theta))
+            }
+            Projection::Equirectangular => {
+                let longitude = u * PI;
+                let latitude = v * PI / 2.;
+                let around = self.dir * longitude.cos() + right * longitude.sin();
+                towards(around * latitude.cos() + up * latitude.sin())
+            }
+            Projection::Cubemap => {
+                let column = ((x / width as f64 * 3.) as usize).min(2);
+                let row = ((y / height as f64 * 2.) as usize).min(1);
+                // -1 to 1 within the face
+                let a = (x / width as f64 * 3. - column as f64) * 2. - 1.;
+                let b = 1. - (y / height as f64 * 2. - row as f64) * 2.;
+                // Forward, right and up of every face
+                let d = self.dir;
+                let (forward, right, up) = [
+                    (right, -d, up),
+                    (-right, d, up),
+                    (up, right, -d),
+                    (-up, right, d),
+                    (d, right, up),
+                    (-d, -right, up),
+                ][row * 3 + column];
+                towards(forward + right * a + up * b)
+            }
+        }
+    }
+
+    /// A perspective ray along `dir`, which has unit length along the view direction
+    ///
+    /// With an aperture the ray starts at a random point on the lens and
+    /// passes the same point on the focus plane.
+    fn through_lens(&self, dir: Vec3, right: Vec3, up: Vec3, random: &mut Random) -> Ray {
+        let lens = self.lens;
+        let focus = self.pos + dir * lens.focus;
+        if lens.aperture <= 0. {
+            return Ray { p: self.pos, q: focus };
+        }
+        let (lx, ly) = if lens.blades >= 3 {
+            random.polygon(lens.blades)
+        } else {
+            random.disk()
+        };
+        let p = self.pos + (right * lx + up * ly) * lens.aperture;
+        // Keep the point the ray passes at least as far away as the pinhole one
+        Ray {
+            p,
+            q: p + (focus - p) / lens.focus,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ray(camera: &Camera, x: f64, y: f64, width: u16, height: u16) -> Ray {
+        camera.ray(x, y, width, height, &mut Random::new(0))
+    }
+
+    fn direction(ray: &Ray) -> Vec3 {
+        (ray.q - ray.p).norm()
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).mag() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    fn camera(fov: Fov) -> Camera {
+        Camera::look_at(
+            Vec3 { x: 1., y: 2., z: 3. },
+            Vec3 { x: 1., y: 2., z: 4. },
+            Vec3::UP,
+            fov,
+        )
+    }
+
+    #[test]
+    fn centre_looks_along_dir() {
+        let ray = ray(&camera(Fov::Vertical(90.)), 100., 50., 200, 100);
+        assert_close(ray.p, Vec3 { x: 1., y: 2., z: 3. });
+        assert_close(direction(&ray), Vec3 { x: 0., y: 0., z: 1. });
+    }
+
+    #[test]
+    fn vertical_fov_takes_width_from_aspect() {
+        let ray = ray(&camera(Fov::Vertical(90.)), 0., 0., 200, 100);
+        assert_close(direction(&ray), Vec3 { x: -2., y: 1., z: 1. }.norm());
+    }
+
+    #[test]
+    fn horizontal_fov_takes_height_from_aspect() {
+        let ray = ray(&camera(Fov::Horizontal(90.)), 150., 25., 200, 100);
+        assert_close(direction(&ray), Vec3 { x: 0.5, y: 0.25, z: 1. }.norm());
+    }
+
+    #[test]
+    fn roll_turns_the_camera_clockwise() {
+        let camera = Camera {
+            roll: 90.,
+            ..camera(Fov::Vertical(90.))
+        };
+        // The top of the image now shows what was to the right
+        let ray = ray(&camera, 50., 0., 100, 100);
+        assert_close(direction(&ray), Vec3 { x: 1., y: 0., z: 1. }.norm());
+    }
+
+    #[test]
+    fn looking_straight_up_is_not_degenerate() {
+        let camera = Camera::look_at(Vec3::NULL, Vec3::UP, Vec3::UP, Fov::Vertical(90.));
+        let (right, up) = camera.basis();
+        assert!((right.mag() - 1.).abs() < 1e-9 && (up.mag() - 1.).abs() < 1e-9);
+        assert!(right.dot(camera.dir).abs() < 1e-9 && up.dot(camera.dir).abs() < 1e-9);
+        let ray = ray(&camera, 0., 0., 100, 100);
+        assert!(direction(&ray).dot(Vec3::UP) > 0.5);
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let camera = Camera {
+            projection: Projection::Orthographic,
+            lens: Lens { focus: 10., ..Lens::PINHOLE },
+            ..camera(Fov::Vertical(90.))
+        };
+        let ray = ray(&camera, 0., 0., 200, 100);
+        assert_close(ray.p, Vec3 { x: -19., y: 12., z: 3. });
+        assert_close(direction(&ray), camera.dir);
+    }
+
+    #[test]
+    fn fisheye_angle_grows_linearly() {
+        let camera = Camera {
+            projection: Projection::Fisheye,
+            ..camera(Fov::Horizontal(180.))
+        };
+        // Half way to the right edge is 45° to the right
+        let ray = ray(&camera, 150., 50., 200, 100);
+        assert_close(direction(&ray), Vec3 { x: 1., y: 0., z: 1. }.norm());
+    }
+
+    #[test]
+    fn equirectangular_covers_all_directions() {
+        let camera = Camera {
+            projection: Projection::Equirectangular,
+            ..camera(Fov::Vertical(90.))
+        };
+        assert_close(direction(&ray(&camera, 100., 50., 200, 100)), camera.dir);
+        assert_close(direction(&ray(&camera, 150., 50., 200, 100)), Vec3 { x: 1., y: 0., z: 0. });
+        assert_close(direction(&ray(&camera, 0., 50., 200, 100)), Vec3 { x: 0., y: 0., z: -1. });
+        assert_close(direction(&ray(&camera, 100., 0., 200, 100)), Vec3::UP);
+    }
+
+    #[test]
+    fn cubemap_faces() {
+        let camera = Camera {
+            projection: Projection::Cubemap,
+            ..camera(Fov::Vertical(90.))
+        };
+        let centre = |column: f64, row: f64| direction(&ray(&camera, 50. + column * 100., 50. + row * 100., 300, 200));
+        assert_close(centre(0., 0.), Vec3 { x: 1., y: 0., z: 0. });
+        assert_close(centre(1., 0.), Vec3 { x: -1., y: 0., z: 0. });
+        assert_close(centre(2., 0.), Vec3::UP);
+        assert_close(centre(0., 1.), -Vec3::UP);
+        assert_close(centre(1., 1.), camera.dir);
+        assert_close(centre(2., 1.), -camera.dir);
+        // Top left corner of the front face
+        let ray = ray(&camera, 100., 100., 300, 200);
+        assert_close(direction(&ray), Vec3 { x: -1., y: 1., z: 1. }.norm());
+    }
+
+    #[test]
+    fn eyes_converge() {
+        let camera = camera(Fov::Vertical(90.));
+        for offset in &[-3., 3.] {
+            let eye = camera.eye(*offset, 10.);
+            assert_close(eye.pos, camera.pos + Vec3 { x: *offset, y: 0., z: 0. });
+            let ray = ray(&eye, 50., 50., 100, 100);
+            // The centre rays of both eyes cross 10 in front of the camera
+            let d = ray.q - ray.p;
+            assert_close(ray.p + d * (10. / d.z), camera.pos + camera.dir * 10.);
+        }
+    }
+
+    #[test]
+    fn lens_rays_meet_on_the_focus_plane() {
+        let camera = Camera {
+            lens: Lens {
+                aperture: 0.5,
+                focus: 10.,
+                blades: 6,
+            },
+            ..camera(Fov::Vertical(90.))
+        };
+        let mut random = Random::new(1);
+        for _ in 0..10 {
+            let ray = camera.ray(25., 75., 100, 100, &mut random);
+            assert!((ray.p - camera.pos).mag() <= 0.5);
+            assert!((ray.p - camera.pos).dot(camera.dir).abs() < 1e-9);
+            // Follow the ray to the focus plane, 10 along the view direction
+            let d = ray.q - ray.p;
+            let k = 10. / d.dot(camera.dir);
+            assert_close(ray.p + d * k, Vec3 { x: -4., y: -3., z: 13. });
+        }
+    }
+}
@@ -0,0 +1,404 @@
+use std::f64::consts::PI;
+use std::path::Path;
+
+use super::animated::{bezier, catmull_rom, neighbours, Easing, Interpolate};
+use super::fingerprint::{Fingerprint, Fnv};
+use super::vec3::Vec3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    /// Passes through every keyframe
+    CatmullRom,
+    /// Keyframes alternate with two handles that pull the curve towards them
+    Bezier,
+}
+
+/// Where the camera is and what it sees
+#[derive(Clone, Copy, Debug)]
+pub struct Shot {
+    pub pos: Vec3,
+    /// The point the camera looks at
+    pub target: Vec3,
+    /// Horizontal field of view in degrees, the camera keeps its own if not given
+    pub fov: Option<f64>,
+    /// Focus distance, the camera keeps its own if not given
+    pub focus: Option<f64>,
+}
+
+/// Keyframed camera motion
+///
+/// Paths are loaded from text files with one keyframe per line:
+///
+/// ```text
+/// # Comments start with a hash
+/// interpolation catmull-rom
+/// easing in-out
+/// key 0 pos 200 40 0 target 0 0 0 fov 65 focus 200
+/// key 2 pos 0 40 200 target 0 0 0 fov 40 focus 200
+/// ```
+///
+/// `interpolation` is `catmull-rom` or `bezier`, `easing` is `linear`, `in`,
+/// `out` or `in-out` over the whole path and `loop` repeats the path. Paths
+/// have at least two keys, each starting with its time, `fov` and `focus` are
+/// optional but have to be given for all keys or none. Bézier paths have two `handle` lines without a time between keys,
+/// which take the same values. Looping paths end with their first keyframe.
+#[derive(Clone, Debug)]
+pub struct CameraPath {
+    pub interpolation: Interpolation,
+    pub easing: Easing,
+    pub looping: bool,
+    /// Times of the keys, ascending
+    times: Vec<f64>,
+    /// Keys, and for Bézier paths the two handles between each pair of keys
+    points: Vec<Shot>,
+}
+
+impl CameraPath {
+    /// Circles the scene once every 2π seconds, bobbing up and down, looking at the origin
+    pub fn orbit() -> CameraPath {
+        let keys = 16;
+        let (times, points) = (0..=keys)
+            .map(|i| {
+                let t = 2. * PI * i as f64 / keys as f64;
+                let pos = Vec3 {
+                    x: 200. * t.cos(),
+                    y: 40. * t.cos(),
+                    z: 200. * t.sin(),
+                };
+                (
+                    t,
+                    Shot {
+                        pos,
+                        target: Vec3::NULL,
+                        fov: None,
+                        focus: None,
+                    },
+                )
+            })
+            .unzip();
+        CameraPath {
+            interpolation: Interpolation::CatmullRom,
+            easing: Easing::Linear,
+            looping: true,
+            times,
+            points,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<CameraPath, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        CameraPath::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<CameraPath, String> {
+        let mut camera_path = CameraPath {
+            interpolation: Interpolation::CatmullRom,
+            easing: Easing::Linear,
+            looping: false,
+            times: vec![],
+            points: vec![],
+        };
+        // Whether each point is a key
+        let mut keys = vec![];
+
+        for (number, line) in text.lines().enumerate() {
+            let error = |message: String| format!("line {}: {}", number + 1, message);
+            let mut words = line.split('#').next().unwrap().split_whitespace();
+            let word = match words.next() {
+                Some(word) => word,
+                None => continue,
+            };
+            match word {
+                "interpolation" => {
+                    camera_path.interpolation = match words.next() {
+                        Some("catmull-rom") => Interpolation::CatmullRom,
+                        Some("bezier") => Interpolation::Bezier,
+                        _ => return Err(error("interpolation must be catmull-rom or bezier".to_string())),
+                    }
+                }
+                "easing" => {
+                    camera_path.easing = match words.next().and_then(Easing::parse) {
+                        Some(easing) => easing,
+                        None => return Err(error("easing must be linear, in, out or in-out".to_string())),
+                    }
+                }
+                "loop" => camera_path.looping = true,
+                "key" | "handle" => {
+                    let mut number = || -> Result<f64, String> {
+                        let word = words.next().ok_or_else(|| error("missing number".to_string()))?;
+                        word.parse().map_err(|_| error(format!("{} is not a number", word)))
+                    };
+                    if word == "key" {
+                        let t = number()?;
+                        if !t.is_finite() {
+                            return Err(error(format!("{} is not a time", t)));
+                        }
+                        if camera_path.times.last().is_some_and(|last| t <= *last) {
+                            return Err(error("keys have to be in order of time".to_string()));
+                        }
+                        camera_path.times.push(t);
+                    }
+                    let mut shot = Shot {
+                        pos: Vec3::NULL,
+                        target: Vec3::NULL,
+                        fov: None,
+                        focus: None,
+                    };
+                    let (mut pos, mut target) = (false, false);
+                    while let Some(field) = words.next() {
+                        let mut number = || -> Result<f64, String> {
+                            let word = words.next().ok_or_else(|| error(format!("{} is missing a number", field)))?;
+                            word.parse().map_err(|_| error(format!("{} is not a number", word)))
+                        };
+                        match field {
+                            "pos" | "target" => {
+                                let v = Vec3 {
+                                    x: number()?,
+                                    y: number()?,
+                                    z: number()?,
+                                };
+                                if field == "pos" {
+                                    shot.pos = v;
+                                    pos = true;
+                                } else {
+                                    shot.target = v;
+                                    target = true;
+                                }
+                            }
+                            "fov" => shot.fov = Some(number()?),
+                            "focus" => {
+                                let focus = number()?;
+                                if focus.is_nan() || focus <= 0. {
+                                    return Err(error(format!("focus {} isn't positive", focus)));
+                                }
+                                shot.focus = Some(focus);
+                            }
+                            _ => return Err(error(format!("unknown field {}", field))),
+                        }
+                    }
+                    if !pos || !target {
+                        return Err(error(format!("{} needs pos and target", word)));
+                    }
+                    if let Some(first) = camera_path.points.first() {
+                        if first.fov.is_some() != shot.fov.is_some() || first.focus.is_some() != shot.focus.is_some() {
+                            return Err(error("fov and focus have to be given for all keys or none".to_string()));
+                        }
+                    }
+                    camera_path.points.push(shot);
+                    keys.push(word == "key");
+                }
+                _ => return Err(error(format!("unknown keyword {}", word))),
+            }
+        }
+
+        // The animation lasts from the first to the last key
+        if camera_path.times.len() < 2 {
+            return Err("a camera path needs at least two keys".to_string());
+        }
+        let expected = match camera_path.interpolation {
+            Interpolation::CatmullRom => (0..camera_path.times.len()).map(|_| true).collect::<Vec<_>>(),
+            Interpolation::Bezier => (0..3 * camera_path.times.len() - 2).map(|i| i % 3 == 0).collect(),
+        };
+        if keys != expected {
+            return Err(match camera_path.interpolation {
+                Interpolation::CatmullRom => "catmull-rom paths have no handles".to_string(),
+                Interpolation::Bezier => "bezier paths need two handles between keys".to_string(),
+            });
+        }
+        Ok(camera_path)
+    }
+
+    /// First and last key time
+    pub fn duration(&self) -> (f64, f64) {
+        (self.times[0], *self.times.last().unwrap())
+    }
+
+    pub fn at(&self, t: f64) -> Shot {
+        let n = self.times.len();
+        let (start, end) = self.duration();
+        let t = if self.looping {
+            start + (t - start).rem_euclid(end - start)
+        } else {
+            t.clamp(start, end)
+        };
+        // Eases the whole path, so the camera doesn't stop at every key
+        let t = start + self.easing.apply((t - start) / (end - start)) * (end - start);
+
+        // The segment between key i and i + 1
+        let i = self.times[..n - 1].iter().rposition(|&time| time <= t).unwrap_or(0);
+        let s = (t - self.times[i]) / (self.times[i + 1] - self.times[i]);
+
+        let p = match self.interpolation {
+            Interpolation::Bezier => [3 * i, 3 * i + 1, 3 * i + 2, 3 * i + 3],
+            Interpolation::CatmullRom => {
+                let [before, after] = neighbours(i, n, self.looping);
+                [before, i, i + 1, after]
+            }
+        };
+        let p = p.map(|i| self.points[i]);
+        let optional = |values: [Option<f64>; 4]| -> Option<f64> {
+            Some(self.curve([values[0]?, values[1]?, values[2]?, values[3]?], s))
+        };
+        Shot {
+            pos: self.curve(p.map(|shot| shot.pos), s),
+            target: self.curve(p.map(|shot| shot.target), s),
+            fov: optional(p.map(|shot| shot.fov)),
+            focus: optional(p.map(|shot| shot.focus)),
+        }
+    }
+
+    /// The segment from the second to the third point
+    fn curve<T: Interpolate>(&self, p: [T; 4], s: f64) -> T {
+        match self.interpolation {
+            Interpolation::Bezier => bezier(p, s),
+            Interpolation::CatmullRom => catmull_rom(p, s),
+        }
+    }
+}
+
+impl Fingerprint for Shot {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Shot { pos, target, fov, focus } = self;
+        pos.fingerprint(h);
+        target.fingerprint(h);
+        fov.fingerprint(h);
+        focus.fingerprint(h);
+    }
+}
+
+impl Fingerprint for CameraPath {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let CameraPath { interpolation, easing, looping, times, points } = self;
+        h.tag(match interpolation {
+            Interpolation::CatmullRom => "catmull-rom",
+            Interpolation::Bezier => "bezier",
+        });
+        easing.fingerprint(h);
+        looping.fingerprint(h);
+        times.fingerprint(h);
+        points.fingerprint(h);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    /// Line and message of the error for a path
+    fn error(text: &str) -> String {
+        CameraPath::parse(text).unwrap_err()
+    }
+
+    #[test]
+    fn keys() {
+        let path = CameraPath::parse(
+            "# A comment\n\
+             \n\
+             key 0 pos 0 0 0 target 0 0 1 fov 60 focus 100 # Trailing comment\n\
+             key 2 pos 20 0 0 target 0 0 1 fov 40 focus 200\n",
+        )
+        .unwrap();
+        assert_eq!(path.interpolation, Interpolation::CatmullRom);
+        assert_eq!(path.easing, Easing::Linear);
+        assert!(!path.looping);
+        assert_eq!(path.duration(), (0., 2.));
+        let shot = path.at(1.);
+        assert!(close(shot.pos.x, 10.) && close(shot.target.z, 1.));
+        assert!(close(shot.fov.unwrap(), 50.) && close(shot.focus.unwrap(), 150.));
+        // Holds the ends
+        assert!(close(path.at(5.).pos.x, 20.));
+    }
+
+    #[test]
+    fn bezier_handles() {
+        let path = CameraPath::parse(
+            "interpolation bezier\n\
+             key 0 pos 0 0 0 target 0 0 0\n\
+             handle pos 0 30 0 target 0 0 0\n\
+             handle pos 30 30 0 target 0 0 0\n\
+             key 1 pos 30 0 0 target 0 0 0\n",
+        )
+        .unwrap();
+        assert_eq!(path.interpolation, Interpolation::Bezier);
+        let shot = path.at(0.5);
+        assert!(close(shot.pos.x, 15.) && close(shot.pos.y, 22.5));
+        assert_eq!(shot.fov, None);
+    }
+
+    #[test]
+    fn easing_and_loop() {
+        let path = CameraPath::parse(
+            "easing in\n\
+             loop\n\
+             key 0 pos 0 0 0 target 0 0 0\n\
+             key 1 pos 10 0 0 target 0 0 0\n\
+             key 2 pos 0 0 0 target 0 0 0\n",
+        )
+        .unwrap();
+        assert_eq!(path.easing, Easing::In);
+        assert!(path.looping);
+        assert!(close(path.at(2.5).pos.x, path.at(0.5).pos.x));
+        assert!(close(path.at(-1.).pos.x, path.at(1.).pos.x));
+        // Easing in, a quarter of the time barely moves
+        assert!(path.at(0.5).pos.x < 1.);
+    }
+
+    #[test]
+    fn errors() {
+        let key = "key 0 pos 0 0 0 target 0 0 0\nkey 1 pos 1 0 0 target 0 0 0\n";
+        assert_eq!(error(""), "a camera path needs at least two keys");
+        assert_eq!(error("key 0 pos 0 0 0 target 0 0 0"), "a camera path needs at least two keys");
+        assert_eq!(error(&format!("{}key 1 pos 0 0 0 target 0 0 0", key)), "line 3: keys have to be in order of time");
+        assert_eq!(error("key nan pos 0 0 0 target 0 0 0"), "line 1: NaN is not a time");
+        assert_eq!(error("key 0 pos 0 x 0 target 0 0 0"), "line 1: x is not a number");
+        assert_eq!(error("key 0 pos 0 0"), "line 1: pos is missing a number");
+        assert_eq!(error("key 0 pos 0 0 0 target 0 0 0 focus 0"), "line 1: focus 0 isn't positive");
+        assert_eq!(error("key 0 target 0 0 0"), "line 1: key needs pos and target");
+        assert_eq!(error(&format!("\n{}", "key 0 pos 0 0 0 target 0 0 0 zoom 2")), "line 2: unknown field zoom");
+        assert_eq!(error(&format!("interpolation linear\n{}", key)), "line 1: interpolation must be catmull-rom or bezier");
+        assert_eq!(error(&format!("easing fast\n{}", key)), "line 1: easing must be linear, in, out or in-out");
+        assert_eq!(error(&format!("{}fly away", key)), "line 3: unknown keyword fly");
+        assert_eq!(
+            error("key 0 pos 0 0 0 target 0 0 0 fov 60\nkey 1 pos 1 0 0 target 0 0 0"),
+            "line 2: fov and focus have to be given for all keys or none"
+        );
+        assert_eq!(error(&format!("interpolation bezier\n{}", key)), "bezier paths need two handles between keys");
+        assert_eq!(
+            error("key 0 pos 0 0 0 target 0 0 0\nhandle pos 0 0 0 target 0 0 0\nkey 1 pos 1 0 0 target 0 0 0"),
+            "catmull-rom paths have no handles"
+        );
+    }
+
+    #[test]
+    fn examples() {
+        for name in ["paths/crane.camera", "paths/flyby.camera"] {
+            let text = std::fs::read_to_string(name).unwrap();
+            assert!(CameraPath::parse(&text).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn missing_file() {
+        assert!(CameraPath::load(Path::new("paths/missing.camera")).is_err());
+    }
+
+    #[test]
+    fn easing_spans_the_path() {
+        let path = CameraPath::parse(
+            "easing in-out\n\
+             key 0 pos 0 0 0 target 0 0 0\n\
+             key 1 pos 10 0 0 target 0 0 0\n\
+             key 2 pos 20 0 0 target 0 0 0\n",
+        )
+        .unwrap();
+        // Slow at the ends, but still moving at the key in the middle
+        assert!(path.at(0.01).pos.x < 0.01);
+        assert!(close(path.at(1.).pos.x, 10.));
+        assert!(path.at(1.01).pos.x - path.at(1.).pos.x > 0.1);
+        assert!(path.at(1.99).pos.x > 19.99);
+    }
+}
@@ -0,0 +1,263 @@
+use super::{
+    fingerprint::{Fingerprint, Fnv},
+    hit::{Hit, Interval, Surface},
+    renderable::Renderable,
+    Ray,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operation {
+    /// Inside either
+    Union,
+    /// Inside both
+    Intersection,
+    /// Inside the first but not the second, which carves its shape out of the first
+    Difference,
+}
+
+impl Operation {
+    fn inside(&self, a: bool, b: bool) -> bool {
+        match self {
+            Operation::Union => a || b,
+            Operation::Intersection => a && b,
+            Operation::Difference => a && !b,
+        }
+    }
+}
+
+impl Fingerprint for Operation {
+    fn fingerprint(&self, h: &mut Fnv) {
+        h.tag(match self {
+            Operation::Union => "union",
+            Operation::Intersection => "intersection",
+            Operation::Difference => "difference",
+        });
+    }
+}
+
+/// Constructive solid geometry, combining two solids into one
+///
+/// Both objects have to be closed solids. Anything without an inside counts as
+/// empty, like planes, quads, disks and cylinders, cones or paraboloids that
+/// aren't capped. Combinations can be combined again.
+#[derive(Debug)]
+pub struct Csg {
+    pub operation: Operation,
+    pub a: Box<dyn Renderable>,
+    pub b: Box<dyn Renderable>,
+}
+
+impl Renderable for Csg {
+    fn intersects(&self, r: &Ray, t: f64) -> Option<Hit> {
+        let u = (r.q - r.p).norm(); // Unit direction vector
+        // The first boundary at least 1 along the ray, leaving the solid if the ray starts inside
+        self.intervals(r, t)
+            .iter()
+            .flat_map(|interval| [interval.enter, interval.exit])
+            .find(|surface| surface.k >= 1.)
+            .map(|surface| surface.hit(u))
+    }
+
+    fn intervals(&self, r: &Ray, t: f64) -> Vec<Interval> {
+        // Walks along the ray through the boundaries of both, keeping track of being inside each
+        let mut boundaries: Vec<(Surface, bool)> = vec![];
+        for (intervals, of_a) in [(self.a.intervals(r, t), true), (self.b.intervals(r, t), false)] {
+            // Spans at a distance that isn't a number can't be placed along the ray
+            for interval in intervals.into_iter().filter(|i| i.enter.k.is_finite() && i.exit.k.is_finite()) {
+                boundaries.push((interval.enter, of_a));
+                boundaries.push((interval.exit, of_a));
+            }
+        }
+        boundaries.sort_by(|(a, _), (b, _)| a.k.total_cmp(&b.k));
+
+        let (mut in_a, mut in_b) = (false, false);
+        let mut enter = None;
+        let mut intervals = vec![];
+        for (mut surface, of_a) in boundaries {
+            let inside = self.operation.inside(in_a, in_b);
+            if of_a {
+                in_a = !in_a;
+            } else {
+                in_b = !in_b;
+            }
+            if self.operation.inside(in_a, in_b) == inside {
+                continue; // Hidden inside or outside of the other solid
+            }
+            if !of_a && self.operation == Operation::Difference {
+                // The inside of the carved out solid is the outside of the result
+                surface.normal = -surface.normal;
+            }
+            match enter.take() {
+                None => enter = Some(surface),
+                Some(enter) => intervals.push(Interval { enter, exit: surface }),
+            }
+        }
+        intervals
+    }
+}
+
+impl Fingerprint for Csg {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Csg { operation, a, b } = self;
+        h.tag("csg");
+        operation.fingerprint(h);
+        a.fingerprint(h);
+        b.fingerprint(h);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracer::{animated::Animated, quadric::Cylinder, sphere::Sphere, vec3::Vec3, Color, Rgb};
+
+    fn ray(p: Vec3, dir: Vec3) -> Ray {
+        Ray { p, q: p + dir }
+    }
+
+    fn v(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).mag() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    fn sphere(x: f64) -> Box<Sphere> {
+        Box::new(Sphere {
+            pos: v(x, 0., 0.).into(),
+            r: Animated::Constant(10.),
+            color: Color::from(Rgb::WHITE).into(),
+            reflectivity: Animated::Constant(0.),
+        })
+    }
+
+    /// Spheres from -10 to 10 and 5 to 25 along x
+    fn csg(operation: Operation) -> Csg {
+        Csg {
+            operation,
+            a: sphere(0.),
+            b: sphere(15.),
+        }
+    }
+
+    /// Where the intervals of a ray along x start and end
+    fn spans(csg: &Csg, r: &Ray) -> Vec<(f64, f64)> {
+        csg.intervals(r, 0.).iter().map(|i| (i.enter.p.x, i.exit.p.x)).collect()
+    }
+
+    fn close(a: Vec<(f64, f64)>, b: Vec<(f64, f64)>) -> bool {
+        a.len() == b.len() && a.iter().zip(&b).all(|(a, b)| (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9)
+    }
+
+    #[test]
+    fn union() {
+        let r = ray(v(-50., 0., 0.), v(1., 0., 0.));
+        assert!(close(spans(&csg(Operation::Union), &r), vec![(-10., 25.)]));
+        let hit = csg(Operation::Union).intersects(&r, 0.).unwrap();
+        assert_close(hit.reflection.p, v(-10., 0., 0.));
+    }
+
+    #[test]
+    fn intersection() {
+        let r = ray(v(-50., 0., 0.), v(1., 0., 0.));
+        assert!(close(spans(&csg(Operation::Intersection), &r), vec![(5., 10.)]));
+        let hit = csg(Operation::Intersection).intersects(&r, 0.).unwrap();
+        assert_close(hit.reflection.p, v(5., 0., 0.));
+        assert_close(hit.normal, v(-1., 0., 0.));
+    }
+
+    #[test]
+    fn difference() {
+        let r = ray(v(-50., 0., 0.), v(1., 0., 0.));
+        assert!(close(spans(&csg(Operation::Difference), &r), vec![(-10., 5.)]));
+        // Swapped, the other end remains
+        let swapped = Csg {
+            operation: Operation::Difference,
+            a: sphere(15.),
+            b: sphere(0.),
+        };
+        assert!(close(spans(&swapped, &r), vec![(10., 25.)]));
+    }
+
+    #[test]
+    fn difference_flips_normals_of_the_second() {
+        let difference = csg(Operation::Difference);
+        // The carved face at 5 belongs to the second sphere, but faces out of the first
+        let interval = difference.intervals(&ray(v(-50., 0., 0.), v(1., 0., 0.)), 0.)[0];
+        assert_close(interval.exit.normal, v(1., 0., 0.));
+
+        // Coming from the other side through the carved out sphere, the ray enters there
+        let hit = difference.intersects(&ray(v(50., 0., 0.), v(-1., 0., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(5., 0., 0.));
+        assert_close(hit.normal, v(1., 0., 0.));
+        assert_close(hit.reflection.q - hit.reflection.p, v(1., 0., 0.));
+    }
+
+    #[test]
+    fn starting_inside() {
+        // Leaves the difference where the second sphere begins
+        let hit = csg(Operation::Difference).intersects(&ray(v(0., 0., 0.), v(1., 0., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(5., 0., 0.));
+        assert_close(hit.normal, v(-1., 0., 0.));
+    }
+
+    #[test]
+    fn misses() {
+        let r = ray(v(-50., 30., 0.), v(1., 0., 0.));
+        for operation in [Operation::Union, Operation::Intersection, Operation::Difference] {
+            assert!(csg(operation).intersects(&r, 0.).is_none());
+        }
+    }
+
+    #[test]
+    fn uncapped_is_empty() {
+        let csg = Csg {
+            operation: Operation::Union,
+            a: sphere(0.),
+            b: Box::new(Cylinder {
+                pos: v(15., -20., 0.).into(),
+                r: Animated::Constant(10.),
+                height: Animated::Constant(40.),
+                capped: false,
+                color: Color::from(Rgb::WHITE).into(),
+                reflectivity: Animated::Constant(0.),
+            }),
+        };
+        assert!(close(spans(&csg, &ray(v(-50., 0., 0.), v(1., 0., 0.))), vec![(-10., 10.)]));
+    }
+
+    /// A broken solid, whose distances aren't numbers
+    #[derive(Debug)]
+    struct Broken;
+
+    impl Renderable for Broken {
+        fn intersects(&self, _r: &Ray, _t: f64) -> Option<Hit> {
+            None
+        }
+
+        fn intervals(&self, r: &Ray, t: f64) -> Vec<Interval> {
+            let mut intervals = sphere(0.).intervals(r, t);
+            for interval in intervals.iter_mut() {
+                interval.enter.k = f64::NAN;
+            }
+            intervals
+        }
+    }
+
+    impl Fingerprint for Broken {
+        fn fingerprint(&self, h: &mut Fnv) {
+            h.tag("broken");
+        }
+    }
+
+    #[test]
+    fn skips_distances_that_arent_numbers() {
+        let csg = Csg {
+            operation: Operation::Union,
+            a: Box::new(Broken),
+            b: sphere(15.),
+        };
+        assert!(close(spans(&csg, &ray(v(-50., 0., 0.), v(1., 0., 0.))), vec![(5., 25.)]));
+    }
+}
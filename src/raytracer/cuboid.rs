@@ -0,0 +1,200 @@
+use super::{
+    animated::Animated,
+    fingerprint::{Fingerprint, Fnv},
+    hit::{Hit, Interval, Surface},
+    renderable::Renderable,
+    vec3::Vec3,
+    Color, Ray,
+};
+
+/// Axis aligned box, which an `Instance` can rotate into an oriented box
+///
+/// Rays starting inside hit the walls from within, so a box can be a room.
+#[derive(Debug)]
+pub struct Cuboid {
+    /// Corner with the smallest coordinates
+    pub min: Animated<Vec3>,
+    /// Corner with the largest coordinates
+    pub max: Animated<Vec3>,
+    pub(super) color: Animated<Color>,
+    pub reflectivity: Animated<f64>,
+}
+
+fn component(v: Vec3, axis: usize) -> f64 {
+    match axis {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
+
+impl Cuboid {
+    /// Distances along the unit direction `u` at which a ray enters and leaves the box, with the axis of the face
+    pub(super) fn slab(r: &Ray, u: Vec3, min: Vec3, max: Vec3) -> Option<((f64, usize), (f64, usize))> {
+        // https://en.wikipedia.org/wiki/Slab_method
+        let (mut near, mut far) = ((f64::NEG_INFINITY, 0), (f64::INFINITY, 0));
+        for axis in 0..3 {
+            let (o, d) = (component(r.p, axis), component(u, axis));
+            let (lo, hi) = (component(min, axis), component(max, axis));
+            if d == 0. {
+                if o < lo || o > hi {
+                    return None; // Parallel to the slab and outside of it
+                }
+                continue;
+            }
+            let (a, b) = ((lo - o) / d, (hi - o) / d);
+            let (a, b) = (a.min(b), a.max(b));
+            if a > near.0 {
+                near = (a, axis);
+            }
+            if b < far.0 {
+                far = (b, axis);
+            }
+        }
+        if near.0 > far.0 {
+            return None; // Misses the box
+        }
+        Some((near, far))
+    }
+
+    /// The point `k` along the ray on the face across `axis`, `leaving` the box through it or not
+    fn surface(&self, r: &Ray, u: Vec3, (min, max): (Vec3, Vec3), (k, axis): (f64, usize), leaving: bool, t: f64) -> Surface {
+        let p = r.p + (u * k);
+        // Pointing out of the box, the way the ray goes when it leaves
+        let sign = component(u, axis).signum() * if leaving { 1. } else { -1. };
+        let normal = match axis {
+            0 => Vec3 { x: sign, y: 0., z: 0. },
+            1 => Vec3 { x: 0., y: sign, z: 0. },
+            _ => Vec3 { x: 0., y: 0., z: sign },
+        };
+        // Position on the face along the two other axes
+        let along = |axis: usize| {
+            let axis = axis % 3;
+            (component(p, axis) - component(min, axis)) / (component(max, axis) - component(min, axis))
+        };
+        Surface {
+            k,
+            p,
+            normal,
+            uv: (along(axis + 1), along(axis + 2)),
+            color: self.color.at(t),
+            reflectivity: self.reflectivity.at(t),
+        }
+    }
+}
+
+impl Renderable for Cuboid {
+    fn intersects(&self, r: &Ray, t: f64) -> Option<Hit> {
+        let corners = (self.min.at(t), self.max.at(t));
+        let u = (r.q - r.p).norm(); // Unit direction vector
+        let (near, far) = Cuboid::slab(r, u, corners.0, corners.1)?;
+
+        // The far side is hit from inside the box
+        let surface = if near.0 >= 1. {
+            self.surface(r, u, corners, near, false, t)
+        } else if far.0 >= 1. {
+            self.surface(r, u, corners, far, true, t)
+        } else {
+            return None; // Behind the start of the ray
+        };
+        Some(surface.hit(u))
+    }
+
+    fn intervals(&self, r: &Ray, t: f64) -> Vec<Interval> {
+        let corners = (self.min.at(t), self.max.at(t));
+        let u = (r.q - r.p).norm(); // Unit direction vector
+        match Cuboid::slab(r, u, corners.0, corners.1) {
+            Some((near, far)) => vec![Interval {
+                enter: self.surface(r, u, corners, near, false, t),
+                exit: self.surface(r, u, corners, far, true, t),
+            }],
+            None => vec![],
+        }
+    }
+}
+
+impl Fingerprint for Cuboid {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Cuboid { min, max, color, reflectivity } = self;
+        h.tag("cuboid");
+        min.fingerprint(h);
+        max.fingerprint(h);
+        color.fingerprint(h);
+        reflectivity.fingerprint(h);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracer::Rgb;
+
+    fn v(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn ray(p: Vec3, dir: Vec3) -> Ray {
+        Ray { p, q: p + dir }
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).mag() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    fn cube() -> Cuboid {
+        Cuboid {
+            min: v(-10., -10., -10.).into(),
+            max: v(10., 10., 10.).into(),
+            color: Color::from(Rgb::WHITE).into(),
+            reflectivity: Animated::Constant(0.),
+        }
+    }
+
+    #[test]
+    fn hit_from_outside() {
+        let hit = cube().intersects(&ray(v(-50., 5., -5.), v(1., 0., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(-10., 5., -5.));
+        assert_close(hit.normal, v(-1., 0., 0.));
+        assert_close(hit.reflection.q - hit.reflection.p, v(-1., 0., 0.));
+        // Across the face along y and z
+        assert!((hit.uv.0 - 0.75).abs() < 1e-9 && (hit.uv.1 - 0.25).abs() < 1e-9);
+
+        let hit = cube().intersects(&ray(v(0., 30., 0.), v(0., -1., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(0., 10., 0.));
+        assert_close(hit.normal, v(0., 1., 0.));
+    }
+
+    #[test]
+    fn hit_from_inside() {
+        // The far wall, with the normal pointing back into the box
+        let hit = cube().intersects(&ray(Vec3::NULL, v(0., 0., 1.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(0., 0., 10.));
+        assert_close(hit.normal, v(0., 0., -1.));
+    }
+
+    #[test]
+    fn parallel_to_faces() {
+        assert!(cube().intersects(&ray(v(-50., 20., 0.), v(1., 0., 0.)), 0.).is_none());
+        let hit = cube().intersects(&ray(v(-50., 9., 9.), v(1., 0., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(-10., 9., 9.));
+    }
+
+    #[test]
+    fn misses() {
+        // Pointing away, passing above and starting just outside the far wall
+        assert!(cube().intersects(&ray(v(-50., 0., 0.), v(-1., 0., 0.)), 0.).is_none());
+        assert!(cube().intersects(&ray(v(-50., 0., 0.), v(1., 1., 0.)), 0.).is_none());
+        assert!(cube().intersects(&ray(v(10.5, 0., 0.), v(1., 0., 0.)), 0.).is_none());
+    }
+
+    #[test]
+    fn intervals() {
+        let intervals = cube().intervals(&ray(v(-50., 0., 0.), v(1., 0., 0.)), 0.);
+        assert_eq!(intervals.len(), 1);
+        let Interval { enter, exit } = intervals[0];
+        assert!((enter.k - 40.).abs() < 1e-9 && (exit.k - 60.).abs() < 1e-9);
+        assert_close(enter.normal, v(-1., 0., 0.));
+        assert_close(exit.normal, v(1., 0., 0.));
+        assert!(cube().intervals(&ray(v(-50., 20., 0.), v(1., 0., 0.)), 0.).is_empty());
+    }
+}
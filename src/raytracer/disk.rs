@@ -0,0 +1,112 @@
+use std::f64::consts::PI;
+
+use super::{animated::Animated, fingerprint::{Fingerprint, Fnv}, hit::Hit, renderable::Renderable, vec3::Vec3, Color, Ray};
+
+/// Flat circle, visible from both sides
+#[derive(Debug)]
+pub struct Disk {
+    pub pos: Animated<Vec3>,
+    /// Normal vector of the disk, normalized after interpolation
+    pub n: Animated<Vec3>,
+    pub r: Animated<f64>,
+    pub(super) color: Animated<Color>,
+    pub reflectivity: Animated<f64>,
+}
+
+impl Renderable for Disk {
+    fn intersects(&self, r: &Ray, t: f64) -> Option<Hit> {
+        let (pos, n, radius) = (self.pos.at(t), self.n.at(t).norm(), self.r.at(t));
+        let u = (r.q - r.p).norm(); // Unit direction vector
+
+        let udotn = u.dot(n);
+        if udotn.abs() < 0.00005 {
+            return None; // Parallel case
+        }
+        let k = (pos - r.p).dot(n) / udotn;
+        if k < 1. {
+            return None; // Behind the start of the ray
+        }
+
+        let intersection = r.p + (u * k);
+        let d = intersection - pos;
+        if d.mag() > radius {
+            return None;
+        }
+        // Angle around the centre and distance from it
+        let (a, b) = n.tangents();
+        let uv = (0.5 + d.dot(b).atan2(d.dot(a)) / (2. * PI), d.mag() / radius);
+        // The normal points back at the ray
+        let n = if udotn > 0. { -n } else { n };
+        Some(Hit::reflect(u, intersection, n, self.color.at(t), self.reflectivity.at(t), uv))
+    }
+}
+
+impl Fingerprint for Disk {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Disk { pos, n, r, color, reflectivity } = self;
+        h.tag("disk");
+        pos.fingerprint(h);
+        n.fingerprint(h);
+        r.fingerprint(h);
+        color.fingerprint(h);
+        reflectivity.fingerprint(h);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracer::Rgb;
+
+    fn v(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn ray(p: Vec3, dir: Vec3) -> Ray {
+        Ray { p, q: p + dir }
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).mag() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    fn disk() -> Disk {
+        Disk {
+            pos: Vec3::NULL.into(),
+            n: v(0., 2., 0.).into(),
+            r: Animated::Constant(10.),
+            color: Color::from(Rgb::WHITE).into(),
+            reflectivity: Animated::Constant(0.),
+        }
+    }
+
+    #[test]
+    fn hit_from_both_sides() {
+        let hit = disk().intersects(&ray(v(3., 50., 4.), v(0., -1., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(3., 0., 4.));
+        assert_close(hit.normal, v(0., 1., 0.));
+        assert_close(hit.reflection.q - hit.reflection.p, v(0., 1., 0.));
+
+        let hit = disk().intersects(&ray(v(3., -50., 4.), v(0., 1., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(3., 0., 4.));
+        assert_close(hit.normal, v(0., -1., 0.));
+    }
+
+    #[test]
+    fn uv_is_angle_and_distance() {
+        let uv = |x: f64, z: f64| disk().intersects(&ray(v(x, 50., z), v(0., -1., 0.)), 0.).unwrap().uv;
+        let (a, b) = (uv(5., 0.), uv(0., 5.));
+        assert!((a.1 - 0.5).abs() < 1e-9 && (b.1 - 0.5).abs() < 1e-9);
+        // A quarter turn around the centre
+        let turn = (a.0 - b.0).rem_euclid(1.);
+        assert!((turn - 0.25).abs() < 1e-9 || (turn - 0.75).abs() < 1e-9);
+        assert!((uv(10., 0.).1 - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn parallel_and_misses() {
+        assert!(disk().intersects(&ray(v(-50., 0., 0.), v(1., 0., 0.)), 0.).is_none());
+        assert!(disk().intersects(&ray(v(8., 50., 8.), v(0., -1., 0.)), 0.).is_none());
+        assert!(disk().intersects(&ray(v(0., 50., 0.), v(0., 1., 0.)), 0.).is_none());
+    }
+}
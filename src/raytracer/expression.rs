@@ -0,0 +1,439 @@
+//! Formulas for animated values, like `r = 60; r * sin(2 * t), r * cos(2 * t), 0`
+//!
+//! A formula is a list of expressions separated by commas, one per component
+//! of the value, optionally preceded by variables defined as `name = expression;`.
+//! Expressions know `+ - * / ^`, parentheses, the time `t`, the constants `pi`
+//! and `e` and the functions `sin`, `cos`, `sqrt`, `abs`, `min` and `max`.
+
+use std::f64::consts::{E, PI};
+use std::fmt;
+
+use super::fingerprint::{Fingerprint, Fnv};
+
+/// A problem in the source of a formula at a column counted from 1
+#[derive(Debug)]
+pub struct ParseError {
+    pub source: String,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    /// Shows the source with a caret under the offending column
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.source)?;
+        writeln!(f, "{:>width$}", "^", width = self.column)?;
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Function {
+    Sin,
+    Cos,
+    Sqrt,
+    Abs,
+    Min,
+    Max,
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Number(f64),
+    Time,
+    /// Index of a variable defined earlier in the formula
+    Variable(usize),
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, t: f64, variables: &[f64]) -> f64 {
+        match self {
+            Expr::Number(x) => *x,
+            Expr::Time => t,
+            Expr::Variable(i) => variables[*i],
+            Expr::Neg(a) => -a.eval(t, variables),
+            Expr::Binary(op, a, b) => {
+                let (a, b) = (a.eval(t, variables), b.eval(t, variables));
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    '/' => a / b,
+                    _ => a.powf(b),
+                }
+            }
+            Expr::Call(function, args) => {
+                let mut args = args.iter().map(|arg| arg.eval(t, variables));
+                match function {
+                    Function::Sin => args.next().unwrap().sin(),
+                    Function::Cos => args.next().unwrap().cos(),
+                    Function::Sqrt => args.next().unwrap().sqrt(),
+                    Function::Abs => args.next().unwrap().abs(),
+                    Function::Min => args.fold(f64::INFINITY, f64::min),
+                    Function::Max => args.fold(f64::NEG_INFINITY, f64::max),
+                }
+            }
+        }
+    }
+}
+
+/// A parsed formula that evaluates to one or more numbers
+#[derive(Clone)]
+pub struct Formula {
+    source: String,
+    /// Variables in the order they are defined, each can use the ones before
+    variables: Vec<Expr>,
+    components: Vec<Expr>,
+    /// Columns where the components start, and the column after the end
+    columns: Vec<usize>,
+}
+
+impl fmt::Debug for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Formula({:?})", self.source)
+    }
+}
+
+impl Formula {
+    pub fn parse(source: &str) -> Result<Formula, ParseError> {
+        let mut parser = Parser {
+            source,
+            chars: source.char_indices().collect(),
+            position: 0,
+            names: vec![],
+        };
+        let mut formula = Formula {
+            source: source.to_string(),
+            variables: vec![],
+            components: vec![],
+            columns: vec![],
+        };
+
+        // Variables are a name followed by `=`
+        loop {
+            let start = parser.position;
+            parser.skip_whitespace();
+            let name_start = parser.position;
+            let name = parser.name();
+            parser.skip_whitespace();
+            if name.is_empty() || parser.peek() != Some('=') {
+                parser.position = start;
+                break;
+            }
+            if name == "t" || constant(&name).is_some() || parser.names.contains(&name) {
+                return Err(parser.error_at(name_start, format!("{} is already defined", name)));
+            }
+            parser.position += 1;
+            formula.variables.push(parser.expression()?);
+            parser.expect(';')?;
+            parser.names.push(name);
+        }
+
+        loop {
+            parser.skip_whitespace();
+            formula.columns.push(parser.position + 1);
+            formula.components.push(parser.expression()?);
+            if parser.peek() != Some(',') {
+                break;
+            }
+            parser.position += 1;
+        }
+        if parser.peek().is_some() {
+            return Err(parser.error("expected an operator, a comma or the end".to_string()));
+        }
+        formula.columns.push(parser.chars.len() + 1);
+        Ok(formula)
+    }
+
+    /// Number of values the formula evaluates to
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    /// Fails at the first extra component, or at the end if some are missing
+    pub fn expect_len(&self, count: usize) -> Result<(), ParseError> {
+        if self.len() == count {
+            return Ok(());
+        }
+        Err(ParseError {
+            source: self.source.clone(),
+            column: self.columns[count.min(self.len())],
+            message: format!("expected {} comma separated values, not {}", count, self.len()),
+        })
+    }
+
+    pub fn eval(&self, t: f64) -> Vec<f64> {
+        let mut variables = Vec::with_capacity(self.variables.len());
+        for variable in &self.variables {
+            let value = variable.eval(t, &variables);
+            variables.push(value);
+        }
+        self.components.iter().map(|c| c.eval(t, &variables)).collect()
+    }
+}
+
+impl Fingerprint for Formula {
+    fn fingerprint(&self, h: &mut Fnv) {
+        // The source defines the rest
+        let Formula {
+            source,
+            variables: _,
+            components: _,
+            columns: _,
+        } = self;
+        source.fingerprint(h);
+    }
+}
+
+fn constant(name: &str) -> Option<f64> {
+    match name {
+        "pi" => Some(PI),
+        "e" => Some(E),
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    chars: Vec<(usize, char)>,
+    /// Index into `chars`
+    position: usize,
+    /// Variables defined so far
+    names: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn error_at(&self, position: usize, message: String) -> ParseError {
+        ParseError {
+            source: self.source.to_string(),
+            column: position + 1,
+            message,
+        }
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        self.error_at(self.position, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.position).is_some_and(|(_, c)| c.is_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    /// The next character that isn't whitespace
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.position).map(|(_, c)| *c)
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ParseError> {
+        if self.peek() != Some(c) {
+            return Err(self.error(format!("expected {}", c)));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn name(&mut self) -> String {
+        let mut name = String::new();
+        while let Some((_, c)) = self.chars.get(self.position) {
+            if !(c.is_alphanumeric() || *c == '_') || (name.is_empty() && c.is_numeric()) {
+                break;
+            }
+            name.push(*c);
+            self.position += 1;
+        }
+        name
+    }
+
+    /// Sums and differences of terms
+    fn expression(&mut self) -> Result<Expr, ParseError> {
+        let mut a = self.term()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.position += 1;
+            a = Expr::Binary(op, Box::new(a), Box::new(self.term()?));
+        }
+        Ok(a)
+    }
+
+    /// Products and quotients of factors
+    fn term(&mut self) -> Result<Expr, ParseError> {
+        let mut a = self.factor()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.position += 1;
+            a = Expr::Binary(op, Box::new(a), Box::new(self.factor()?));
+        }
+        Ok(a)
+    }
+
+    /// Negation and powers, which bind to the right
+    fn factor(&mut self) -> Result<Expr, ParseError> {
+        if self.peek() == Some('-') {
+            self.position += 1;
+            return Ok(Expr::Neg(Box::new(self.factor()?)));
+        }
+        let a = self.atom()?;
+        if self.peek() == Some('^') {
+            self.position += 1;
+            return Ok(Expr::Binary('^', Box::new(a), Box::new(self.factor()?)));
+        }
+        Ok(a)
+    }
+
+    fn atom(&mut self) -> Result<Expr, ParseError> {
+        let start = self.position;
+        match self.peek() {
+            Some('(') => {
+                self.position += 1;
+                let a = self.expression()?;
+                self.expect(')')?;
+                Ok(a)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let start = self.position;
+                while self
+                    .chars
+                    .get(self.position)
+                    .is_some_and(|(_, c)| c.is_ascii_digit() || *c == '.')
+                {
+                    self.position += 1;
+                }
+                let from = self.chars[start].0;
+                let to = self.chars.get(self.position).map_or(self.source.len(), |(i, _)| *i);
+                self.source[from..to]
+                    .parse()
+                    .map(Expr::Number)
+                    .map_err(|_| self.error_at(start, format!("{} is not a number", &self.source[from..to])))
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let start = self.position;
+                let name = self.name();
+                if self.peek() == Some('(') {
+                    return self.call(start, &name);
+                }
+                if name == "t" {
+                    Ok(Expr::Time)
+                } else if let Some(value) = constant(&name) {
+                    Ok(Expr::Number(value))
+                } else if let Some(i) = self.names.iter().position(|n| *n == name) {
+                    Ok(Expr::Variable(i))
+                } else {
+                    Err(self.error_at(start, format!("unknown variable {}", name)))
+                }
+            }
+            Some(c) => Err(self.error_at(start, format!("expected a number, a name or ( but found {}", c))),
+            None => Err(self.error("expected a number, a name or ( but the formula ends".to_string())),
+        }
+    }
+
+    fn call(&mut self, start: usize, name: &str) -> Result<Expr, ParseError> {
+        let (function, arity) = match name {
+            "sin" => (Function::Sin, Some(1)),
+            "cos" => (Function::Cos, Some(1)),
+            "sqrt" => (Function::Sqrt, Some(1)),
+            "abs" => (Function::Abs, Some(1)),
+            "min" => (Function::Min, None),
+            "max" => (Function::Max, None),
+            _ => return Err(self.error_at(start, format!("unknown function {}", name))),
+        };
+        self.expect('(')?;
+        let mut args = vec![self.expression()?];
+        while self.peek() == Some(',') {
+            self.position += 1;
+            args.push(self.expression()?);
+        }
+        self.expect(')')?;
+        match arity {
+            Some(n) if args.len() != n => {
+                Err(self.error_at(start, format!("{} takes {} argument, not {}", name, n, args.len())))
+            }
+            None if args.len() < 2 => Err(self.error_at(start, format!("{} takes at least 2 arguments", name))),
+            _ => Ok(Expr::Call(function, args)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(source: &str, t: f64) -> Vec<f64> {
+        Formula::parse(source).unwrap().eval(t)
+    }
+
+    fn close(a: Vec<f64>, b: &[f64]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-9)
+    }
+
+    /// Column and message of the error for a source
+    fn error(source: &str) -> (usize, String) {
+        let e = Formula::parse(source).unwrap_err();
+        (e.column, e.message)
+    }
+
+    #[test]
+    fn precedence() {
+        assert!(close(eval("1 + 2 * 3", 0.), &[7.]));
+        assert!(close(eval("(1 + 2) * 3", 0.), &[9.]));
+        assert!(close(eval("8 / 4 / 2", 0.), &[1.]));
+        assert!(close(eval("10 - 4 - 3", 0.), &[3.]));
+        // Powers bind to the right and tighter than products
+        assert!(close(eval("2 ^ 3 ^ 2", 0.), &[512.]));
+        assert!(close(eval("3 * 2 ^ 2", 0.), &[12.]));
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert!(close(eval("-2 ^ 2", 0.), &[-4.]));
+        assert!(close(eval("--3", 0.), &[3.]));
+        assert!(close(eval("4 * -t", 2.), &[-8.]));
+        assert!(close(eval("1 - -1", 0.), &[2.]));
+    }
+
+    #[test]
+    fn variables_and_time() {
+        assert!(close(eval("r = 2 * t; s = r + 1; r, s, t", 3.), &[6., 7., 3.]));
+        assert!(close(eval("pi, e", 0.), &[PI, E]));
+    }
+
+    #[test]
+    fn functions() {
+        assert!(close(eval("sin(pi / 2), cos(0), sqrt(16), abs(-3)", 0.), &[1., 1., 4., 3.]));
+        assert!(close(eval("min(3, 1, 2), max(t, 2)", 5.), &[1., 5.]));
+    }
+
+    #[test]
+    fn error_columns() {
+        assert_eq!(error("1 + "), (5, "expected a number, a name or ( but the formula ends".to_string()));
+        assert_eq!(error("2 * x").0, 5);
+        assert_eq!(error("2 * foo(1)").0, 5);
+        assert_eq!(error("sin(1, 2)").0, 1);
+        assert_eq!(error("max(1)").0, 1);
+        assert_eq!(error("(1 + 2").0, 7);
+        assert_eq!(error("1 2").0, 3);
+        assert_eq!(error("1..2 + t").0, 1);
+        assert_eq!(error("1 + $").0, 5);
+        assert_eq!(error("a = 1; a = 2; a").0, 8);
+        assert_eq!(error("  t = 1; t").0, 3);
+    }
+
+    #[test]
+    fn component_count() {
+        let formula = Formula::parse("1, 2,  3").unwrap();
+        assert!(formula.expect_len(3).is_ok());
+        // Points at the first extra component
+        assert_eq!(formula.expect_len(2).unwrap_err().column, 8);
+        // Or past the end when there are too few
+        assert_eq!(formula.expect_len(4).unwrap_err().column, 9);
+    }
+
+    #[test]
+    fn caret() {
+        let e = Formula::parse("t * y").unwrap_err();
+        assert_eq!(e.to_string(), "t * y\n    ^\ncolumn 5: unknown variable y");
+    }
+}
@@ -0,0 +1,259 @@
+//! Hashes of everything that changes how a scene renders
+//!
+//! Manifests compare these across machines, so unlike `DefaultHasher` or
+//! `Debug` output they don't depend on the platform or the Rust version.
+
+use std::rc::Rc;
+
+use super::{vec3::Vec3, Color};
+
+/// 64-bit FNV-1a, see http://www.isthe.com/chongo/tech/comp/fnv/
+pub struct Fnv(u64);
+
+impl Fnv {
+    pub fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    /// Tells apart variants of enums and kinds of objects with the same fields
+    pub fn tag(&mut self, name: &str) {
+        name.fingerprint(self);
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Values that feed their fields into a hash in a fixed order
+///
+/// Implementations destructure their values without `..`, so a field added
+/// later doesn't compile until it is hashed or explicitly left out.
+pub trait Fingerprint {
+    fn fingerprint(&self, h: &mut Fnv);
+}
+
+impl Fingerprint for f64 {
+    fn fingerprint(&self, h: &mut Fnv) {
+        h.bytes(&self.to_bits().to_le_bytes());
+    }
+}
+
+impl Fingerprint for u64 {
+    fn fingerprint(&self, h: &mut Fnv) {
+        h.bytes(&self.to_le_bytes());
+    }
+}
+
+impl Fingerprint for u32 {
+    fn fingerprint(&self, h: &mut Fnv) {
+        (*self as u64).fingerprint(h);
+    }
+}
+
+impl Fingerprint for u16 {
+    fn fingerprint(&self, h: &mut Fnv) {
+        (*self as u64).fingerprint(h);
+    }
+}
+
+impl Fingerprint for usize {
+    fn fingerprint(&self, h: &mut Fnv) {
+        (*self as u64).fingerprint(h);
+    }
+}
+
+impl Fingerprint for bool {
+    fn fingerprint(&self, h: &mut Fnv) {
+        h.bytes(&[*self as u8]);
+    }
+}
+
+impl Fingerprint for str {
+    fn fingerprint(&self, h: &mut Fnv) {
+        // The length keeps consecutive strings apart
+        self.len().fingerprint(h);
+        h.bytes(self.as_bytes());
+    }
+}
+
+impl Fingerprint for String {
+    fn fingerprint(&self, h: &mut Fnv) {
+        self.as_str().fingerprint(h);
+    }
+}
+
+impl Fingerprint for Vec3 {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Vec3 { x, y, z } = self;
+        x.fingerprint(h);
+        y.fingerprint(h);
+        z.fingerprint(h);
+    }
+}
+
+impl Fingerprint for Color {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Color { r, g, b } = self;
+        r.fingerprint(h);
+        g.fingerprint(h);
+        b.fingerprint(h);
+    }
+}
+
+impl<T: Fingerprint> Fingerprint for [T] {
+    fn fingerprint(&self, h: &mut Fnv) {
+        self.len().fingerprint(h);
+        for value in self {
+            value.fingerprint(h);
+        }
+    }
+}
+
+impl<T: Fingerprint> Fingerprint for Vec<T> {
+    fn fingerprint(&self, h: &mut Fnv) {
+        self[..].fingerprint(h);
+    }
+}
+
+impl<T: Fingerprint> Fingerprint for Option<T> {
+    fn fingerprint(&self, h: &mut Fnv) {
+        match self {
+            Some(value) => {
+                true.fingerprint(h);
+                value.fingerprint(h);
+            }
+            None => false.fingerprint(h),
+        }
+    }
+}
+
+impl<A: Fingerprint, B: Fingerprint> Fingerprint for (A, B) {
+    fn fingerprint(&self, h: &mut Fnv) {
+        self.0.fingerprint(h);
+        self.1.fingerprint(h);
+    }
+}
+
+impl<T: Fingerprint + ?Sized> Fingerprint for Box<T> {
+    fn fingerprint(&self, h: &mut Fnv) {
+        (**self).fingerprint(h);
+    }
+}
+
+impl<T: Fingerprint + ?Sized> Fingerprint for Rc<T> {
+    fn fingerprint(&self, h: &mut Fnv) {
+        (**self).fingerprint(h);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracer::{
+        animated::Animated,
+        camera::{Fov, Projection},
+        sphere::Sphere,
+        CameraPath, Quality, Raytracer, Rgb,
+    };
+    use crate::renderer::Renderer;
+
+    fn hash(value: &impl Fingerprint) -> u64 {
+        let mut h = Fnv::new();
+        value.fingerprint(&mut h);
+        h.finish()
+    }
+
+    #[test]
+    fn known_values() {
+        // From the reference implementation
+        let mut h = Fnv::new();
+        assert_eq!(h.finish(), 0xcbf29ce484222325);
+        h.bytes(b"a");
+        assert_eq!(h.finish(), 0xaf63dc4c8601ec8c);
+        let mut h = Fnv::new();
+        h.bytes(b"foobar");
+        assert_eq!(h.finish(), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn order_and_grouping_matter() {
+        assert_ne!(hash(&(1., 2.)), hash(&(2., 1.)));
+        assert_ne!(hash(&("ab".to_string(), "c".to_string())), hash(&("a".to_string(), "bc".to_string())));
+        assert_ne!(hash(&vec![vec![1.], vec![]]), hash(&vec![vec![], vec![1.]]));
+        assert_ne!(hash(&0.), hash(&-0.));
+    }
+
+    #[test]
+    fn scenes() {
+        assert_eq!(Raytracer::new().scene_hash(), Raytracer::new().scene_hash());
+        let mut slower = Raytracer::new();
+        slower.shutter = 0.5;
+        assert_ne!(slower.scene_hash(), Raytracer::new().scene_hash());
+        let mut showcase = Raytracer::new();
+        showcase.showcase();
+        assert_ne!(showcase.scene_hash(), Raytracer::new().scene_hash());
+    }
+
+    #[test]
+    fn every_field_counts() {
+        let sphere = || Sphere {
+            pos: Vec3::NULL.into(),
+            r: Animated::Constant(1.),
+            color: Color::from(Rgb::WHITE).into(),
+            reflectivity: Animated::Constant(0.),
+        };
+        let changed = [
+            Sphere { pos: Vec3::UP.into(), ..sphere() },
+            Sphere { r: Animated::Constant(2.), ..sphere() },
+            Sphere { color: Color::from(Rgb::BLACK).into(), ..sphere() },
+            Sphere { reflectivity: Animated::Constant(0.5), ..sphere() },
+        ];
+        for other in &changed {
+            assert_ne!(hash(other), hash(&sphere()));
+        }
+
+        let camera = Raytracer::new().camera;
+        let mut changed = vec![camera; 7];
+        changed[0].up = Vec3::NULL;
+        changed[1].fov = Fov::Vertical(40.);
+        changed[2].projection = Projection::Fisheye;
+        changed[3].roll = 10.;
+        changed[4].shift = 0.1;
+        changed[5].lens.aperture = 2.;
+        changed[6].scripted = false;
+        for other in changed {
+            let mut renderer = Raytracer::new();
+            renderer.camera = other;
+            assert_ne!(renderer.scene_hash(), Raytracer::new().scene_hash());
+        }
+    }
+
+    #[test]
+    fn frames_dont_change_the_hash() {
+        let mut renderer = Raytracer::new();
+        renderer.quality = Quality {
+            width: 8,
+            height: 6,
+            samples: 1,
+            depth: 2,
+        };
+        renderer.camera_path = CameraPath::parse(
+            "key 0 pos 200 0 0 target 0 0 0 fov 40 focus 150\n\
+             key 1 pos 0 0 200 target 0 0 0 fov 70 focus 250\n",
+        )
+        .unwrap();
+        let before = renderer.scene_hash();
+        renderer.render_hdr(0.5);
+        assert_eq!(renderer.scene_hash(), before);
+        renderer.render_hdr(1.);
+        assert_eq!(renderer.scene_hash(), before);
+    }
+}
@@ -0,0 +1,378 @@
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+
+use super::{
+    animated::Animated,
+    cuboid::Cuboid,
+    fingerprint::{Fingerprint, Fnv},
+    hit::{Hit, Surface},
+    renderable::Renderable,
+    vec3::Vec3,
+    Color, Ray, Rgb,
+};
+
+/// Terrain from a grid of heights, like a grayscale image where white is high
+///
+/// Every cell of four neighbouring grid points is split into two triangles,
+/// shaded with normals interpolated from the grid points.
+#[derive(Clone)]
+pub struct Heightfield {
+    /// Number of grid points along x and z
+    columns: usize,
+    rows: usize,
+    /// Heights of the grid points row by row, from 0 to 1
+    heights: Vec<f64>,
+    /// Normals at the grid points, from the slopes to their neighbours
+    normals: Vec<Vec3>,
+    /// The highest height, which bounds the terrain
+    top: f64,
+    /// Corner with the smallest x and z, at height 0
+    pub pos: Vec3,
+    /// Extent along x and z, and the height of white
+    pub size: Vec3,
+    /// Texture coordinates repeat this often across the terrain, 0 leaves them out
+    pub tiles: f64,
+    pub(super) color: Animated<Color>,
+    pub reflectivity: Animated<f64>,
+}
+
+impl fmt::Debug for Heightfield {
+    /// Sums up the heights instead of listing them, the fingerprint hashes all of them
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Heightfield")
+            .field("columns", &self.columns)
+            .field("rows", &self.rows)
+            .field("heights", &self.heights.iter().sum::<f64>())
+            .field("pos", &self.pos)
+            .field("size", &self.size)
+            .field("tiles", &self.tiles)
+            .field("color", &self.color)
+            .field("reflectivity", &self.reflectivity)
+            .finish()
+    }
+}
+
+impl Heightfield {
+    pub fn new(columns: usize, rows: usize, heights: Vec<f64>, pos: Vec3, size: Vec3) -> Heightfield {
+        assert!(columns >= 2 && rows >= 2, "A heightfield needs at least 2x2 points");
+        assert_eq!(heights.len(), columns * rows);
+        let top = heights.iter().fold(0f64, |a, b| a.max(*b));
+        let mut heightfield = Heightfield {
+            columns,
+            rows,
+            heights,
+            normals: vec![],
+            top,
+            pos,
+            size,
+            tiles: 1.,
+            color: Color::from(Rgb {
+                r: 0x6b,
+                g: 0x8e,
+                b: 0x4e,
+            })
+            .into(),
+            reflectivity: Animated::Constant(0.1),
+        };
+        heightfield.normals = (0..rows)
+            .flat_map(|z| (0..columns).map(move |x| (x, z)))
+            .map(|(x, z)| heightfield.slope(x, z))
+            .collect();
+        heightfield
+    }
+
+    /// Reads the heights from the brightness of a PNG
+    pub fn load(path: &Path, pos: Vec3, size: Vec3) -> Result<Heightfield, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let mut decoder = png::Decoder::new(file);
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let (info, mut reader) = decoder.read_info().map_err(|e| e.to_string())?;
+        let mut bytes = vec![0; info.buffer_size()];
+        reader.next_frame(&mut bytes).map_err(|e| e.to_string())?;
+
+        // Palettes are expanded to colours while decoding
+        let channels = match info.color_type {
+            png::ColorType::Grayscale => 1,
+            png::ColorType::GrayscaleAlpha => 2,
+            png::ColorType::RGB => 3,
+            png::ColorType::RGBA => 4,
+            other => return Err(format!("unsupported colour type {:?}", other)),
+        };
+        // Alpha is ignored, colours are averaged
+        let colors = channels.min(3);
+        let (columns, rows) = (info.width as usize, info.height as usize);
+        if columns < 2 || rows < 2 {
+            return Err(format!("a heightmap needs at least 2x2 pixels, not {}x{}", columns, rows));
+        }
+        let heights = match info.bit_depth {
+            png::BitDepth::Sixteen => bytes
+                .chunks(2 * channels)
+                .map(|p| (0..colors).map(|c| u16::from_be_bytes([p[2 * c], p[2 * c + 1]]) as f64).sum::<f64>())
+                .map(|sum| sum / (colors as f64 * u16::MAX as f64))
+                .collect(),
+            _ => bytes
+                .chunks(channels)
+                .map(|p| p[..colors].iter().map(|v| *v as f64).sum::<f64>() / (colors as f64 * 255.))
+                .collect(),
+        };
+        Ok(Heightfield::new(columns, rows, heights, pos, size))
+    }
+
+    /// Distance between neighbouring grid points along x and z
+    fn cell(&self) -> (f64, f64) {
+        (
+            self.size.x / (self.columns - 1) as f64,
+            self.size.z / (self.rows - 1) as f64,
+        )
+    }
+
+    fn point(&self, x: usize, z: usize) -> Vec3 {
+        let (cx, cz) = self.cell();
+        self.pos
+            + Vec3 {
+                x: x as f64 * cx,
+                y: self.heights[z * self.columns + x] * self.size.y,
+                z: z as f64 * cz,
+            }
+    }
+
+    /// Normal from the central differences of the heights, one sided at the edges
+    fn slope(&self, x: usize, z: usize) -> Vec3 {
+        let (cx, cz) = self.cell();
+        let height = |x: usize, z: usize| self.heights[z * self.columns + x] * self.size.y;
+        let (x0, x1) = (x.saturating_sub(1), (x + 1).min(self.columns - 1));
+        let (z0, z1) = (z.saturating_sub(1), (z + 1).min(self.rows - 1));
+        Vec3 {
+            x: -(height(x1, z) - height(x0, z)) / ((x1 - x0) as f64 * cx),
+            y: 1.,
+            z: -(height(x, z1) - height(x, z0)) / ((z1 - z0) as f64 * cz),
+        }
+        .norm()
+    }
+
+    /// Intersection with the triangle of the grid points `a`, `b` and `c`, given as column and row
+    fn triangle(&self, r: &Ray, u: Vec3, t: f64, [a, b, c]: [(usize, usize); 3]) -> Option<Surface> {
+        // https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
+        let (p0, p1, p2) = (self.point(a.0, a.1), self.point(b.0, b.1), self.point(c.0, c.1));
+        let (e1, e2) = (p1 - p0, p2 - p0);
+        let h = u.cross(e2);
+        let det = e1.dot(h);
+        if det.abs() < 1e-12 {
+            return None; // Parallel case
+        }
+        let s = r.p - p0;
+        let b1 = s.dot(h) / det;
+        let q = s.cross(e1);
+        let b2 = u.dot(q) / det;
+        if b1 < 0. || b2 < 0. || b1 + b2 > 1. {
+            return None;
+        }
+        let k = e2.dot(q) / det;
+
+        // Interpolated between the corners
+        let b0 = 1. - b1 - b2;
+        let normal = self.normals[a.1 * self.columns + a.0] * b0
+            + self.normals[b.1 * self.columns + b.0] * b1
+            + self.normals[c.1 * self.columns + c.0] * b2;
+        let grid = |i: usize| {
+            let (a, b, c) = ([a.0, a.1][i] as f64, [b.0, b.1][i] as f64, [c.0, c.1][i] as f64);
+            a * b0 + b * b1 + c * b2
+        };
+        let uv = (
+            grid(0) / (self.columns - 1) as f64 * self.tiles,
+            grid(1) / (self.rows - 1) as f64 * self.tiles,
+        );
+        Some(Surface {
+            k,
+            p: r.p + u * k,
+            normal: normal.norm(),
+            uv,
+            color: self.color.at(t),
+            reflectivity: self.reflectivity.at(t),
+        })
+    }
+}
+
+impl Renderable for Heightfield {
+    fn intersects(&self, r: &Ray, t: f64) -> Option<Hit> {
+        let u = (r.q - r.p).norm(); // Unit direction vector
+        let max = self.pos + Vec3 { y: self.top * self.size.y, ..self.size };
+        let ((near, _), (far, _)) = Cuboid::slab(r, u, self.pos, max)?;
+        if far < 1. {
+            return None; // Behind the start of the ray
+        }
+
+        // https://en.wikipedia.org/wiki/Digital_differential_analyzer_(graphics_algorithm)
+        // Walks through the cells under the ray in the order it crosses them
+        let (cx, cz) = self.cell();
+        let start = r.p + u * near.max(0.);
+        let cell = |v: f64, size: f64, count: usize| ((v / size).floor().max(0.) as usize).min(count - 2);
+        let (mut x, mut z) = (
+            cell(start.x - self.pos.x, cx, self.columns),
+            cell(start.z - self.pos.z, cz, self.rows),
+        );
+        // Distance along the ray to the next cell boundary and between boundaries, for x and z
+        let axis = |d: f64, o: f64, i: usize, size: f64| {
+            if d == 0. {
+                (f64::INFINITY, f64::INFINITY)
+            } else {
+                let boundary = (i + if d > 0. { 1 } else { 0 }) as f64 * size;
+                ((boundary - o) / d, size / d.abs())
+            }
+        };
+        let (mut next_x, step_x) = axis(u.x, r.p.x - self.pos.x, x, cx);
+        let (mut next_z, step_z) = axis(u.z, r.p.z - self.pos.z, z, cz);
+
+        loop {
+            let nearest = [
+                [(x, z), (x + 1, z), (x + 1, z + 1)],
+                [(x, z), (x + 1, z + 1), (x, z + 1)],
+            ]
+            .iter()
+            .filter_map(|corners| self.triangle(r, u, t, *corners))
+            .filter(|surface| surface.k >= 1.)
+            .min_by(|a, b| a.k.partial_cmp(&b.k).unwrap());
+            if let Some(surface) = nearest {
+                return Some(surface.hit(u));
+            }
+
+            if next_x.min(next_z) > far {
+                return None;
+            }
+            if next_x < next_z {
+                if (u.x > 0. && x + 2 >= self.columns) || (u.x < 0. && x == 0) {
+                    return None;
+                }
+                x = if u.x > 0. { x + 1 } else { x - 1 };
+                next_x += step_x;
+            } else {
+                if (u.z > 0. && z + 2 >= self.rows) || (u.z < 0. && z == 0) {
+                    return None;
+                }
+                z = if u.z > 0. { z + 1 } else { z - 1 };
+                next_z += step_z;
+            }
+        }
+    }
+}
+
+impl Fingerprint for Heightfield {
+    fn fingerprint(&self, h: &mut Fnv) {
+        // Normals and the top follow from the heights
+        let Heightfield {
+            columns,
+            rows,
+            heights,
+            normals: _,
+            top: _,
+            pos,
+            size,
+            tiles,
+            color,
+            reflectivity,
+        } = self;
+        h.tag("heightfield");
+        columns.fingerprint(h);
+        rows.fingerprint(h);
+        heights.fingerprint(h);
+        pos.fingerprint(h);
+        size.fingerprint(h);
+        tiles.fingerprint(h);
+        color.fingerprint(h);
+        reflectivity.fingerprint(h);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracer::fingerprint::Fnv;
+
+    fn ray(p: Vec3, dir: Vec3) -> Ray {
+        Ray { p, q: p + dir }
+    }
+
+    fn v(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).mag() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    /// 3x3 points at half height, 1 above the ground
+    fn flat() -> Heightfield {
+        Heightfield::new(3, 3, vec![0.5; 9], Vec3::NULL, v(10., 2., 10.))
+    }
+
+    /// 5x5 points 10 apart, flat at 0 but for a peak of 10 at x 30, z 20
+    fn peak() -> Heightfield {
+        let mut heights = vec![0.; 25];
+        heights[2 * 5 + 3] = 1.;
+        Heightfield::new(5, 5, heights, Vec3::NULL, v(40., 10., 40.))
+    }
+
+    #[test]
+    fn from_above() {
+        let hit = flat().intersects(&ray(v(2.5, 10., 7.5), v(0., -1., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(2.5, 1., 7.5));
+        assert_close(hit.normal, v(0., 1., 0.));
+        assert_close(hit.reflection.q - hit.reflection.p, v(0., 1., 0.));
+        assert!((hit.uv.0 - 0.25).abs() < 1e-9 && (hit.uv.1 - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn from_outside_the_bounds() {
+        // Comes in through the side of the bounding box and goes down to the surface
+        let hit = flat().intersects(&ray(v(-5., 3., 5.), v(1., -0.2, 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(5., 1., 5.));
+        assert!(flat().intersects(&ray(v(-5., 3., 5.), v(-1., -0.2, 0.)), 0.).is_none());
+        assert!(flat().intersects(&ray(v(-5., 3., 5.), v(1., 0.2, 0.)), 0.).is_none());
+    }
+
+    #[test]
+    fn walks_to_the_peak() {
+        // Level below the top, only the slopes of the peak are in the way
+        for (start, dir) in [
+            (v(-5., 5., 21.), v(1., 0., 0.)),
+            (v(45., 5., 21.), v(-1., 0., 0.)),
+            (v(-15., 5., -10.), v(3., 0., 2.)),
+            (v(30.5, 5., 45.), v(0., 0., -1.)),
+        ] {
+            let hit = peak().intersects(&ray(start, dir), 0.).unwrap();
+            let p = hit.reflection.p;
+            assert!((p.y - 5.).abs() < 1e-9);
+            assert!(p.x > 20. && p.x < 40. && p.z > 10. && p.z < 30., "{:?}", p);
+            // Facing back at the ray
+            assert!(hit.normal.dot(dir) < 0.);
+        }
+    }
+
+    #[test]
+    fn misses() {
+        // Above the peak, over flat ground and along the ground away from the peak
+        assert!(peak().intersects(&ray(v(-5., 11., 21.), v(1., 0., 0.)), 0.).is_none());
+        assert!(peak().intersects(&ray(v(-5., 5., 5.), v(1., 0., 0.)), 0.).is_none());
+        assert!(peak().intersects(&ray(v(-5., 5., 21.), v(-1., 0., 0.)), 0.).is_none());
+    }
+
+    #[test]
+    fn fingerprint_sees_every_height() {
+        let hash = |heights: Vec<f64>| {
+            let mut h = Fnv::new();
+            Heightfield::new(2, 2, heights, Vec3::NULL, v(1., 1., 1.)).fingerprint(&mut h);
+            h.finish()
+        };
+        // Same sum, different terrain
+        assert_ne!(hash(vec![0., 1., 0., 0.]), hash(vec![1., 0., 0., 0.]));
+    }
+
+    #[test]
+    fn load() {
+        let hills = Heightfield::load(Path::new("assets/hills.png"), Vec3::NULL, v(1., 1., 1.)).unwrap();
+        assert!(hills.columns >= 2 && hills.rows >= 2);
+        assert!(hills.heights.iter().all(|h| (0. ..=1.).contains(h)));
+        assert!(Heightfield::load(Path::new("assets/missing.png"), Vec3::NULL, v(1., 1., 1.)).is_err());
+    }
+}
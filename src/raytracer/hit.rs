@@ -1,10 +1,55 @@
-use super::{Ray, Rgb};
+use super::{vec3::Vec3, Color, Ray};
 
 pub struct Hit {
     pub(in super) reflection: Ray,
-    pub(in super) color: Rgb,
+    pub(in super) color: Color,
     pub reflectivity: f64,
+    /// Surface normal at the intersection
+    pub normal: Vec3,
+    /// Texture coordinates at the intersection, from 0 to 1 across bounded surfaces
+    pub uv: (f64, f64),
 }
 
 impl Hit {
+    /// Mirrors the unit direction `u` of a ray at the point `p` of a surface with the unit normal `n`
+    pub(in super) fn reflect(u: Vec3, p: Vec3, n: Vec3, color: Color, reflectivity: f64, uv: (f64, f64)) -> Hit {
+        Hit {
+            reflection: Ray {
+                p,
+                q: p + u - n * (2. * u.dot(n)),
+            },
+            color,
+            reflectivity,
+            normal: n,
+            uv,
+        }
+    }
+}
+
+/// A point where a ray enters or leaves a solid
+#[derive(Clone, Copy, Debug)]
+pub struct Surface {
+    /// Distance along the unit direction of the ray, negative behind its start
+    pub k: f64,
+    pub p: Vec3,
+    /// Unit normal pointing out of the solid
+    pub normal: Vec3,
+    pub uv: (f64, f64),
+    pub(in super) color: Color,
+    pub reflectivity: f64,
+}
+
+impl Surface {
+    /// The hit of a ray with the unit direction `u`, with the normal turned towards the ray
+    pub(in super) fn hit(&self, u: Vec3) -> Hit {
+        let n = if self.normal.dot(u) > 0. { -self.normal } else { self.normal };
+        Hit::reflect(u, self.p, n, self.color, self.reflectivity, self.uv)
+    }
+}
+
+/// A span of a ray inside a solid
+#[derive(Clone, Copy, Debug)]
+pub struct Interval {
+    pub enter: Surface,
+    pub exit: Surface,
 }
@@ -0,0 +1,187 @@
+use std::rc::Rc;
+
+use super::{
+    fingerprint::{Fingerprint, Fnv},
+    hit::{Hit, Interval, Surface},
+    mat::{Mat3, Mat4},
+    renderable::Renderable,
+    Ray,
+};
+
+/// Places a shared object in the world with a transform
+///
+/// Rays are moved into the space of the object and hits back out, so a
+/// scaled sphere becomes an ellipsoid with correct normals and reflections.
+#[derive(Debug)]
+pub struct Instance {
+    object: Rc<dyn Renderable>,
+    transform: Mat4,
+    inverse: Mat4,
+    normal_matrix: Mat3,
+}
+
+impl Instance {
+    /// None if the transform flattens the object, which leaves nothing to hit
+    pub fn new(object: Rc<dyn Renderable>, transform: Mat4) -> Option<Instance> {
+        Some(Instance {
+            object,
+            transform,
+            inverse: transform.inverse()?,
+            normal_matrix: transform.normal_matrix()?,
+        })
+    }
+
+    /// The ray in the space of the object
+    fn local(&self, ray: &Ray) -> Ray {
+        Ray {
+            p: self.inverse.transform_point(ray.p),
+            q: self.inverse.transform_point(ray.q),
+        }
+    }
+}
+
+impl Renderable for Instance {
+    fn intersects(&self, ray: &Ray, t: f64) -> Option<Hit> {
+        let u = (ray.q - ray.p).norm();
+        // Objects skip hits closer than 1 along the ray in their own units, which the scale
+        // stretches by `s` along this ray. Moving the start back by `1 - s` makes that 1 in the world.
+        let local = self.local(ray);
+        let direction = local.q - local.p;
+        let s = direction.mag() / (ray.q - ray.p).mag();
+        let start = local.p - direction.norm() * (1. - s);
+        let hit = self.object.intersects(&Ray { p: start, q: start + direction }, t)?;
+
+        let p = self.transform.transform_point(hit.reflection.p);
+        if (p - ray.p).dot(u) < 1. {
+            return None; // Rounding put it behind the start of the ray
+        }
+        let normal = (self.normal_matrix * hit.normal).norm();
+        // Reflect in world space, non-uniform scaling would bend the reflection from object space
+        let reflection = u - normal * (2. * u.dot(normal));
+        Some(Hit {
+            reflection: Ray { p, q: p + reflection },
+            normal,
+            ..hit
+        })
+    }
+
+    fn intervals(&self, ray: &Ray, t: f64) -> Vec<Interval> {
+        let u = (ray.q - ray.p).norm();
+        // Distances along the ray change with the scale of the transform
+        let world = |surface: Surface| {
+            let p = self.transform.transform_point(surface.p);
+            Surface {
+                k: (p - ray.p).dot(u),
+                p,
+                normal: (self.normal_matrix * surface.normal).norm(),
+                ..surface
+            }
+        };
+        self.object
+            .intervals(&self.local(ray), t)
+            .into_iter()
+            .map(|interval| Interval {
+                enter: world(interval.enter),
+                exit: world(interval.exit),
+            })
+            .collect()
+    }
+}
+
+impl Fingerprint for Instance {
+    fn fingerprint(&self, h: &mut Fnv) {
+        // The inverses follow from the transform
+        let Instance {
+            object,
+            transform,
+            inverse: _,
+            normal_matrix: _,
+        } = self;
+        h.tag("instance");
+        object.fingerprint(h);
+        transform.fingerprint(h);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracer::{animated::Animated, sphere::Sphere, vec3::Vec3, Color, Rgb};
+
+    fn ray(p: Vec3, dir: Vec3) -> Ray {
+        Ray { p, q: p + dir }
+    }
+
+    fn v(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).mag() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    fn unit_sphere(transform: Mat4) -> Instance {
+        let sphere = Sphere {
+            pos: Vec3::NULL.into(),
+            r: Animated::Constant(1.),
+            color: Color::from(Rgb::WHITE).into(),
+            reflectivity: Animated::Constant(0.),
+        };
+        Instance::new(Rc::new(sphere), transform).unwrap()
+    }
+
+    #[test]
+    fn scaled_sphere() {
+        let ellipsoid = unit_sphere(Mat4::scale(v(30., 10., 15.)));
+        let hit = ellipsoid.intersects(&ray(v(-50., 0., 0.), v(1., 0., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(-30., 0., 0.));
+        assert_close(hit.normal, v(-1., 0., 0.));
+
+        // Off the axes the normal follows the squashed surface, not the direction from the centre
+        let hit = ellipsoid.intersects(&ray(v(-50., 5., 0.), v(1., 0., 0.)), 0.).unwrap();
+        let p = hit.reflection.p;
+        assert!(((p.x / 30.).powi(2) + (p.y / 10.).powi(2) - 1.).abs() < 1e-9);
+        assert_close(hit.normal, v(p.x / 900., p.y / 100., 0.).norm());
+    }
+
+    #[test]
+    fn flat_transform_has_no_instance() {
+        let sphere = Sphere {
+            pos: Vec3::NULL.into(),
+            r: Animated::Constant(1.),
+            color: Color::from(Rgb::WHITE).into(),
+            reflectivity: Animated::Constant(0.),
+        };
+        assert!(Instance::new(Rc::new(sphere), Mat4::scale(v(0., 1., 1.))).is_none());
+    }
+
+    #[test]
+    fn scaled_up_hits_close_by() {
+        // 5 away in the world is less than 1 in the space of the sphere
+        let ellipsoid = unit_sphere(Mat4::scale(v(30., 10., 15.)));
+        let hit = ellipsoid.intersects(&ray(v(-35., 0., 0.), v(1., 0., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(-30., 0., 0.));
+    }
+
+    #[test]
+    fn scaled_down_skips_own_surface() {
+        // Starting 0.5 in front of a tiny sphere is too close in the world
+        let tiny = unit_sphere(Mat4::scale(v(0.1, 0.1, 0.1)));
+        assert!(tiny.intersects(&ray(v(-0.6, 0., 0.), v(1., 0., 0.)), 0.).is_none());
+        assert!(tiny.intersects(&ray(v(-2., 0., 0.), v(1., 0., 0.)), 0.).is_some());
+    }
+
+    #[test]
+    fn rotated_and_moved() {
+        let transform = Mat4::translate(v(0., 0., 100.))
+            * Mat4::rotate(Vec3::UP, std::f64::consts::FRAC_PI_2)
+            * Mat4::scale(v(20., 1., 1.));
+        let object = unit_sphere(transform);
+        // Turned a quarter around y, the long axis lies along z
+        let hit = object.intersects(&ray(v(0., 0., 0.), v(0., 0., 1.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(0., 0., 80.));
+        assert_close(hit.normal, v(0., 0., -1.));
+        assert_close(hit.reflection.q - hit.reflection.p, v(0., 0., -1.));
+        assert!(object.intersects(&ray(v(5., 0., 0.), v(0., 0., 1.)), 0.).is_none());
+    }
+}
@@ -0,0 +1,246 @@
+use super::fingerprint::{Fingerprint, Fnv};
+use super::vec3::Vec3;
+
+/// 3x3 matrix, stored row by row
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat3 {
+    pub m: [[f64; 3]; 3],
+}
+
+impl Mat3 {
+    pub const IDENTITY: Mat3 = Mat3 {
+        m: [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]],
+    };
+
+    pub fn transpose(&self) -> Mat3 {
+        let m = &self.m;
+        Mat3 {
+            m: [
+                [m[0][0], m[1][0], m[2][0]],
+                [m[0][1], m[1][1], m[2][1]],
+                [m[0][2], m[1][2], m[2][2]],
+            ],
+        }
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// None if the matrix can't be inverted, like a scale by 0
+    pub fn inverse(&self) -> Option<Mat3> {
+        let m = &self.m;
+        let det = self.determinant();
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        // Transposed cofactors divided by the determinant
+        let c = |r0: usize, r1: usize, c0: usize, c1: usize| (m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]) / det;
+        Some(Mat3 {
+            m: [
+                [c(1, 2, 1, 2), -c(0, 2, 1, 2), c(0, 1, 1, 2)],
+                [-c(1, 2, 0, 2), c(0, 2, 0, 2), -c(0, 1, 0, 2)],
+                [c(1, 2, 0, 1), -c(0, 2, 0, 1), c(0, 1, 0, 1)],
+            ],
+        })
+    }
+}
+
+impl std::ops::Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+    fn mul(self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3 {
+            x: m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            y: m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            z: m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        }
+    }
+}
+
+impl std::ops::Mul<Mat3> for Mat3 {
+    type Output = Mat3;
+    fn mul(self, rhs: Mat3) -> Mat3 {
+        let mut m = [[0.; 3]; 3];
+        for (r, row) in m.iter_mut().enumerate() {
+            for (c, v) in row.iter_mut().enumerate() {
+                *v = (0..3).map(|i| self.m[r][i] * rhs.m[i][c]).sum();
+            }
+        }
+        Mat3 { m }
+    }
+}
+
+/// 4x4 matrix for affine transforms of column vectors, stored row by row
+///
+/// `a * b` applies `b` first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        m: [[1., 0., 0., 0.], [0., 1., 0., 0.], [0., 0., 1., 0.], [0., 0., 0., 1.]],
+    };
+
+    /// Combines a linear part with a translation
+    pub fn affine(linear: Mat3, translation: Vec3) -> Mat4 {
+        let l = &linear.m;
+        Mat4 {
+            m: [
+                [l[0][0], l[0][1], l[0][2], translation.x],
+                [l[1][0], l[1][1], l[1][2], translation.y],
+                [l[2][0], l[2][1], l[2][2], translation.z],
+                [0., 0., 0., 1.],
+            ],
+        }
+    }
+
+    pub fn translate(v: Vec3) -> Mat4 {
+        Mat4::affine(Mat3::IDENTITY, v)
+    }
+
+    pub fn scale(v: Vec3) -> Mat4 {
+        Mat4::affine(
+            Mat3 {
+                m: [[v.x, 0., 0.], [0., v.y, 0.], [0., 0., v.z]],
+            },
+            Vec3::NULL,
+        )
+    }
+
+    /// Rotates around a unit axis, in the same direction as `Vec3::rotate`
+    pub fn rotate(axis: Vec3, angle: f64) -> Mat4 {
+        let (sin, cos) = angle.sin_cos();
+        let Vec3 { x, y, z } = axis;
+        let k = 1. - cos;
+        Mat4::affine(
+            Mat3 {
+                m: [
+                    [cos + x * x * k, x * y * k - z * sin, x * z * k + y * sin],
+                    [y * x * k + z * sin, cos + y * y * k, y * z * k - x * sin],
+                    [z * x * k - y * sin, z * y * k + x * sin, cos + z * z * k],
+                ],
+            },
+            Vec3::NULL,
+        )
+    }
+
+    /// The upper left 3x3 part, which transforms directions
+    pub fn linear(&self) -> Mat3 {
+        let m = &self.m;
+        Mat3 {
+            m: [
+                [m[0][0], m[0][1], m[0][2]],
+                [m[1][0], m[1][1], m[1][2]],
+                [m[2][0], m[2][1], m[2][2]],
+            ],
+        }
+    }
+
+    pub fn translation(&self) -> Vec3 {
+        Vec3 {
+            x: self.m[0][3],
+            y: self.m[1][3],
+            z: self.m[2][3],
+        }
+    }
+
+    /// Inverse of an affine transform, None if it flattens space
+    pub fn inverse(&self) -> Option<Mat4> {
+        let linear = self.linear().inverse()?;
+        Some(Mat4::affine(linear, -(linear * self.translation())))
+    }
+
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        self.linear() * p + self.translation()
+    }
+
+    /// Transforms normals so they stay perpendicular to transformed surfaces
+    pub fn normal_matrix(&self) -> Option<Mat3> {
+        self.linear().inverse().map(|m| m.transpose())
+    }
+}
+
+impl std::ops::Mul<Mat4> for Mat4 {
+    type Output = Mat4;
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut m = [[0.; 4]; 4];
+        for (r, row) in m.iter_mut().enumerate() {
+            for (c, v) in row.iter_mut().enumerate() {
+                *v = (0..4).map(|i| self.m[r][i] * rhs.m[i][c]).sum();
+            }
+        }
+        Mat4 { m }
+    }
+}
+
+impl Fingerprint for Mat4 {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Mat4 { m } = self;
+        for row in m {
+            for value in row {
+                value.fingerprint(h);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).mag() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    fn transform() -> Mat4 {
+        Mat4::translate(v(5., -3., 2.)) * Mat4::rotate(v(0., 1., 0.), 0.7) * Mat4::scale(v(2., 3., 0.5))
+    }
+
+    #[test]
+    fn inverse_undoes_transform() {
+        let m = transform();
+        let inverse = m.inverse().unwrap();
+        let p = v(1., 2., 3.);
+        assert_close(inverse.transform_point(m.transform_point(p)), p);
+        assert_close(m.transform_point(inverse.transform_point(p)), p);
+        let identity = m * inverse;
+        for r in 0..4 {
+            for c in 0..4 {
+                assert!((identity.m[r][c] - Mat4::IDENTITY.m[r][c]).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn rotate_matches_vec3() {
+        let axis = v(1., 2., -1.).norm();
+        let p = v(3., -1., 4.);
+        assert_close(Mat4::rotate(axis, 1.2).transform_point(p), p.rotate(axis, 1.2));
+    }
+
+    #[test]
+    fn normals_stay_perpendicular() {
+        let m = transform();
+        // Two directions along a surface and its normal
+        let (a, b) = (v(1., 0., 0.), v(0., 1., 1.));
+        let n = a.cross(b);
+        let n = m.normal_matrix().unwrap() * n;
+        assert!(n.dot(m.linear() * a).abs() < 1e-9);
+        assert!(n.dot(m.linear() * b).abs() < 1e-9);
+    }
+
+    #[test]
+    fn flat_scale_cant_be_inverted() {
+        assert!(Mat4::scale(v(1., 0., 1.)).inverse().is_none());
+        assert!(Mat4::scale(v(1., 0., 1.)).normal_matrix().is_none());
+    }
+}
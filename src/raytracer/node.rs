@@ -0,0 +1,178 @@
+use std::rc::Rc;
+
+use super::{
+    animated::Animated,
+    fingerprint::{Fingerprint, Fnv},
+    instance::Instance, mat::Mat4, renderable::Renderable, vec3::Vec3};
+
+/// One step of the transform of a node
+#[derive(Debug)]
+pub enum Transform {
+    Translate(Animated<Vec3>),
+    /// Around a unit axis by an angle in radians
+    Rotate(Vec3, Animated<f64>),
+    Scale(Animated<Vec3>),
+}
+
+impl Transform {
+    fn at(&self, t: f64) -> Mat4 {
+        match self {
+            Transform::Translate(v) => Mat4::translate(v.at(t)),
+            Transform::Rotate(axis, angle) => Mat4::rotate(*axis, angle.at(t)),
+            Transform::Scale(v) => Mat4::scale(v.at(t)),
+        }
+    }
+}
+
+/// Part of a scene graph
+///
+/// Objects and children are placed by the transform of the node, which is
+/// itself placed by the transforms of all its parents, so a moon can orbit a
+/// planet that orbits a sun.
+#[derive(Debug, Default)]
+pub struct Node {
+    /// Applied from last to first, like matrices multiplied in this order
+    pub transforms: Vec<Transform>,
+    pub objects: Vec<Rc<dyn Renderable>>,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    /// Transform from the space of the node to the space of its parent
+    pub fn local(&self, t: f64) -> Mat4 {
+        self.transforms.iter().fold(Mat4::IDENTITY, |m, transform| m * transform.at(t))
+    }
+
+    /// Every object in the tree placed in world space at time t
+    pub fn flatten(&self, t: f64) -> Vec<Instance> {
+        let mut instances = vec![];
+        self.collect(Mat4::IDENTITY, t, &mut instances);
+        instances
+    }
+
+    fn collect(&self, parent: Mat4, t: f64, instances: &mut Vec<Instance>) {
+        let transform = parent * self.local(t);
+        // Objects scaled flat at this time are skipped until they grow back
+        instances.extend(self.objects.iter().filter_map(|object| Instance::new(object.clone(), transform)));
+        for child in &self.children {
+            child.collect(transform, t, instances);
+        }
+    }
+}
+
+impl Fingerprint for Transform {
+    fn fingerprint(&self, h: &mut Fnv) {
+        match self {
+            Transform::Translate(v) => {
+                h.tag("translate");
+                v.fingerprint(h);
+            }
+            Transform::Rotate(axis, angle) => {
+                h.tag("rotate");
+                axis.fingerprint(h);
+                angle.fingerprint(h);
+            }
+            Transform::Scale(v) => {
+                h.tag("scale");
+                v.fingerprint(h);
+            }
+        }
+    }
+}
+
+impl Fingerprint for Node {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Node { transforms, objects, children } = self;
+        transforms.fingerprint(h);
+        objects.fingerprint(h);
+        children.fingerprint(h);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracer::{sphere::Sphere, Color, Ray, Rgb};
+    use std::f64::consts::FRAC_PI_2;
+
+    fn v(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn unit_sphere() -> Rc<dyn Renderable> {
+        Rc::new(Sphere {
+            pos: Vec3::NULL.into(),
+            r: Animated::Constant(1.),
+            color: Color::from(Rgb::WHITE).into(),
+            reflectivity: Animated::Constant(0.),
+        })
+    }
+
+    /// Where a ray from p in direction dir first hits the instance
+    fn hit(instance: &Instance, p: Vec3, dir: Vec3) -> Vec3 {
+        instance.intersects(&Ray { p, q: p + dir }, 0.).unwrap().reflection.p
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).mag() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn parents_apply_after_children() {
+        // Scaled by the child first, then moved by the parent, so the scale doesn't move it
+        let graph = Node {
+            transforms: vec![Transform::Translate(v(100., 0., 0.).into())],
+            children: vec![Node {
+                transforms: vec![Transform::Scale(v(2., 2., 2.).into())],
+                objects: vec![unit_sphere()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let instances = graph.flatten(0.);
+        assert_eq!(instances.len(), 1);
+        assert_close(hit(&instances[0], Vec3::NULL, v(1., 0., 0.)), v(98., 0., 0.));
+    }
+
+    #[test]
+    fn transforms_of_a_node_apply_last_to_first() {
+        let node = Node {
+            transforms: vec![
+                Transform::Translate(v(100., 0., 0.).into()),
+                Transform::Scale(v(2., 2., 2.).into()),
+            ],
+            objects: vec![unit_sphere()],
+            ..Default::default()
+        };
+        assert_close(hit(&node.flatten(0.)[0], Vec3::NULL, v(1., 0., 0.)), v(98., 0., 0.));
+    }
+
+    #[test]
+    fn time_reaches_every_level() {
+        // The parent turns a quarter around z by t = pi / 2, carrying the child with it
+        let graph = Node {
+            transforms: vec![Transform::Rotate(v(0., 0., 1.), Animated::formula("t").unwrap())],
+            children: vec![Node {
+                transforms: vec![Transform::Translate(Animated::formula("10 + 10 * t, 0, 0").unwrap())],
+                objects: vec![unit_sphere()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert_close(hit(&graph.flatten(0.)[0], Vec3::NULL, v(1., 0., 0.)), v(9., 0., 0.));
+        let distance = 10. + 10. * FRAC_PI_2;
+        let p = hit(&graph.flatten(FRAC_PI_2)[0], Vec3::NULL, v(0., 1., 0.));
+        assert_close(p, v(0., distance - 1., 0.));
+    }
+
+    #[test]
+    fn flat_objects_are_skipped() {
+        let node = Node {
+            transforms: vec![Transform::Scale(Animated::formula("t, 1, 1").unwrap())],
+            objects: vec![unit_sphere()],
+            ..Default::default()
+        };
+        assert!(node.flatten(0.).is_empty());
+        assert_eq!(node.flatten(1.).len(), 1);
+    }
+}
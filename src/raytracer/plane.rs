@@ -1,28 +1,30 @@
-use super::{hit::Hit, renderable::Renderable, vec3::Vec3, Ray, Rgb};
+use super::{animated::Animated, fingerprint::{Fingerprint, Fnv}, hit::Hit, renderable::Renderable, vec3::Vec3, Color, Ray, Rgb};
 
+#[derive(Debug)]
 pub struct Plane {
     /// A point on the plane
-    pub pos: Vec3,
-    /// Normal Vector of the plane
-    pub n: Vec3,
-    pub(super) color: Rgb,
-    pub reflectivity: f64,
+    pub pos: Animated<Vec3>,
+    /// Normal Vector of the plane, normalized after interpolation
+    pub n: Animated<Vec3>,
+    pub(super) color: Animated<Color>,
+    pub reflectivity: Animated<f64>,
     pub checker: bool,
 }
 
 impl Plane {}
 
 impl Renderable for Plane {
-    fn intersects(&self, r: &Ray, _t: f64) -> Option<Hit> {
+    fn intersects(&self, r: &Ray, t: f64) -> Option<Hit> {
+        let (pos, n) = (self.pos.at(t), self.n.at(t).norm());
         // https://en.wikipedia.org/wiki/Line%E2%80%93sphere_intersection
         let u = (r.q - r.p).norm(); // Unit direction vector
 
-        if u.theta(&self.n) < 90.0_f64.to_radians() {
+        if u.theta(&n) < 90.0_f64.to_radians() {
             // Ray comes from behind plane
             return None;
         }
 
-        let udotn = u.dot(self.n);
+        let udotn = u.dot(n);
 
         let epsilon = 0.00005;
         if udotn.abs() < epsilon {
@@ -30,7 +32,7 @@ impl Renderable for Plane {
             return None;
         }
 
-        let k = (self.pos - r.p).dot(self.n) / udotn;
+        let k = (pos - r.p).dot(n) / udotn;
 
         if k < 1. {
             // Behind the start of the ray
@@ -38,7 +40,7 @@ impl Renderable for Plane {
         }
 
         let intersection = r.p + (u * k);
-        let reflection: Vec3 = u - (self.n * (udotn * 2.));
+        let reflection: Vec3 = u - (n * (udotn * 2.));
 
         let color;
         let reflectivity;
@@ -47,11 +49,11 @@ impl Renderable for Plane {
                 + (intersection.z / 100.).floor() as i64)
                 % 2
                 == 0;
-            color = if black { Rgb::BLACK } else { Rgb::WHITE };
-            reflectivity = if black { self.reflectivity } else { 0.3 };
+            color = if black { Rgb::BLACK } else { Rgb::WHITE }.into();
+            reflectivity = if black { self.reflectivity.at(t) } else { 0.3 };
         } else {
-            color = self.color;
-            reflectivity = self.reflectivity;
+            color = self.color.at(t);
+            reflectivity = self.reflectivity.at(t);
         }
 
         Some(Hit {
@@ -61,6 +63,24 @@ impl Renderable for Plane {
             },
             color,
             reflectivity,
+            normal: n,
+            // Distances along the plane from pos, one per checker square
+            uv: {
+                let (a, b) = n.tangents();
+                ((intersection - pos).dot(a) / 100., (intersection - pos).dot(b) / 100.)
+            },
         })
     }
 }
+
+impl Fingerprint for Plane {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Plane { pos, n, color, reflectivity, checker } = self;
+        h.tag("plane");
+        pos.fingerprint(h);
+        n.fingerprint(h);
+        color.fingerprint(h);
+        reflectivity.fingerprint(h);
+        checker.fingerprint(h);
+    }
+}
@@ -0,0 +1,117 @@
+use super::{animated::Animated, fingerprint::{Fingerprint, Fnv}, hit::Hit, renderable::Renderable, vec3::Vec3, Color, Ray};
+
+/// Parallelogram spanned by two edges from a corner, a rectangle if they are perpendicular
+///
+/// This is a bounded piece of a plane, visible from both sides.
+#[derive(Debug)]
+pub struct Quad {
+    pub corner: Animated<Vec3>,
+    /// Edge along which u goes from 0 to 1
+    pub a: Animated<Vec3>,
+    /// Edge along which v goes from 0 to 1
+    pub b: Animated<Vec3>,
+    pub(super) color: Animated<Color>,
+    pub reflectivity: Animated<f64>,
+}
+
+impl Renderable for Quad {
+    fn intersects(&self, r: &Ray, t: f64) -> Option<Hit> {
+        let (corner, a, b) = (self.corner.at(t), self.a.at(t), self.b.at(t));
+        let u = (r.q - r.p).norm(); // Unit direction vector
+        let normal = a.cross(b);
+        let n = normal.norm();
+
+        let udotn = u.dot(n);
+        if udotn.abs() < 0.00005 {
+            return None; // Parallel case
+        }
+        let k = (corner - r.p).dot(n) / udotn;
+        if k < 1. {
+            return None; // Behind the start of the ray
+        }
+
+        let intersection = r.p + (u * k);
+        // Coordinates of the intersection in terms of the edges
+        let d = intersection - corner;
+        let w = normal / normal.dot(normal);
+        let uv = (w.dot(d.cross(b)), w.dot(a.cross(d)));
+        if !(0. ..=1.).contains(&uv.0) || !(0. ..=1.).contains(&uv.1) {
+            return None;
+        }
+        // The normal points back at the ray
+        let n = if udotn > 0. { -n } else { n };
+        Some(Hit::reflect(u, intersection, n, self.color.at(t), self.reflectivity.at(t), uv))
+    }
+}
+
+impl Fingerprint for Quad {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Quad { corner, a, b, color, reflectivity } = self;
+        h.tag("quad");
+        corner.fingerprint(h);
+        a.fingerprint(h);
+        b.fingerprint(h);
+        color.fingerprint(h);
+        reflectivity.fingerprint(h);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracer::Rgb;
+
+    fn v(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn ray(p: Vec3, dir: Vec3) -> Ray {
+        Ray { p, q: p + dir }
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).mag() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    fn quad(b: Vec3) -> Quad {
+        Quad {
+            corner: Vec3::NULL.into(),
+            a: v(20., 0., 0.).into(),
+            b: b.into(),
+            color: Color::from(Rgb::WHITE).into(),
+            reflectivity: Animated::Constant(0.),
+        }
+    }
+
+    #[test]
+    fn hit_from_both_sides() {
+        let rectangle = quad(v(0., 10., 0.));
+        let hit = rectangle.intersects(&ray(v(5., 5., 50.), v(0., 0., -1.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(5., 5., 0.));
+        assert_close(hit.normal, v(0., 0., 1.));
+        assert!((hit.uv.0 - 0.25).abs() < 1e-9 && (hit.uv.1 - 0.5).abs() < 1e-9);
+
+        let hit = rectangle.intersects(&ray(v(5., 5., -50.), v(0., 0., 1.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(5., 5., 0.));
+        assert_close(hit.normal, v(0., 0., -1.));
+        assert_close(hit.reflection.q - hit.reflection.p, v(0., 0., -1.));
+    }
+
+    #[test]
+    fn uv_follows_slanted_edges() {
+        let parallelogram = quad(v(10., 10., 0.));
+        let hit = parallelogram.intersects(&ray(v(15., 5., 50.), v(0., 0., -1.)), 0.).unwrap();
+        assert!((hit.uv.0 - 0.5).abs() < 1e-9 && (hit.uv.1 - 0.5).abs() < 1e-9);
+        // Inside the bounding rectangle, but left of the slanted edge
+        assert!(parallelogram.intersects(&ray(v(2., 8., 50.), v(0., 0., -1.)), 0.).is_none());
+    }
+
+    #[test]
+    fn parallel_and_misses() {
+        let rectangle = quad(v(0., 10., 0.));
+        assert!(rectangle.intersects(&ray(v(-50., 5., 0.), v(1., 0., 0.)), 0.).is_none());
+        assert!(rectangle.intersects(&ray(v(25., 5., 50.), v(0., 0., -1.)), 0.).is_none());
+        assert!(rectangle.intersects(&ray(v(5., -1., 50.), v(0., 0., -1.)), 0.).is_none());
+        assert!(rectangle.intersects(&ray(v(5., 5., 50.), v(0., 0., 1.)), 0.).is_none());
+    }
+}
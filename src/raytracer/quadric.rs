@@ -0,0 +1,454 @@
+use std::f64::consts::PI;
+
+use super::{
+    animated::Animated,
+    fingerprint::{Fingerprint, Fnv},
+    hit::{Hit, Interval, Surface},
+    renderable::Renderable,
+    roots,
+    vec3::Vec3,
+    Color, Ray,
+};
+
+/// Cylinder standing on `pos`, going up the y axis, which an `Instance` can turn
+#[derive(Debug)]
+pub struct Cylinder {
+    /// Centre of the bottom
+    pub pos: Animated<Vec3>,
+    pub r: Animated<f64>,
+    pub height: Animated<f64>,
+    /// Closes the top and bottom with disks
+    pub capped: bool,
+    pub(super) color: Animated<Color>,
+    pub reflectivity: Animated<f64>,
+}
+
+/// Cone standing on its base at `pos`, with the tip `height` above it
+#[derive(Debug)]
+pub struct Cone {
+    /// Centre of the base
+    pub pos: Animated<Vec3>,
+    /// Radius of the base
+    pub r: Animated<f64>,
+    pub height: Animated<f64>,
+    /// Closes the base with a disk
+    pub capped: bool,
+    pub(super) color: Animated<Color>,
+    pub reflectivity: Animated<f64>,
+}
+
+/// Bowl with its lowest point at `pos`, opening up the y axis
+#[derive(Debug)]
+pub struct Paraboloid {
+    pub pos: Animated<Vec3>,
+    /// Radius of the rim
+    pub r: Animated<f64>,
+    pub height: Animated<f64>,
+    /// Closes the rim with a disk
+    pub capped: bool,
+    pub(super) color: Animated<Color>,
+    pub reflectivity: Animated<f64>,
+}
+
+/// Where a ray meets a surface, with the normal pointing out of the shape
+struct Candidate {
+    k: f64,
+    n: Vec3,
+    uv: (f64, f64),
+}
+
+/// Points where a ray from `o` along `u` crosses the side of a shape between 0 and `height`
+///
+/// The side is where `a k² + b k + c` is 0, `gradient` points out of the shape at a point.
+fn side(
+    [a, b, c]: [f64; 3],
+    o: Vec3,
+    u: Vec3,
+    height: f64,
+    gradient: impl Fn(Vec3) -> Vec3,
+    candidates: &mut Vec<Candidate>,
+) {
+    for k in roots::quadratic(a, b, c) {
+        let p = o + u * k;
+        if p.y >= 0. && p.y <= height {
+            candidates.push(Candidate {
+                k,
+                n: gradient(p).norm(),
+                // Angle around the axis and height
+                uv: (0.5 + p.z.atan2(p.x) / (2. * PI), p.y / height),
+            });
+        }
+    }
+}
+
+/// Point where a ray from `o` along `u` crosses a disk of radius `r` at height `y`, facing `up` or down
+fn cap(o: Vec3, u: Vec3, y: f64, r: f64, up: bool, candidates: &mut Vec<Candidate>) {
+    if u.y == 0. {
+        return; // Parallel case
+    }
+    let k = (y - o.y) / u.y;
+    let p = o + u * k;
+    if p.x * p.x + p.z * p.z <= r * r {
+        candidates.push(Candidate {
+            k,
+            n: Vec3 {
+                x: 0.,
+                y: if up { 1. } else { -1. },
+                z: 0.,
+            },
+            uv: (0.5 + p.x / (2. * r), 0.5 + p.z / (2. * r)),
+        });
+    }
+}
+
+/// The candidates as surfaces of a ray with the unit direction `u`, in order along the ray
+fn surfaces(candidates: Vec<Candidate>, r: &Ray, u: Vec3, color: Color, reflectivity: f64) -> Vec<Surface> {
+    let mut surfaces: Vec<Surface> = candidates
+        .into_iter()
+        .map(|candidate| Surface {
+            k: candidate.k,
+            p: r.p + u * candidate.k,
+            normal: candidate.n,
+            uv: candidate.uv,
+            color,
+            reflectivity,
+        })
+        .collect();
+    surfaces.sort_by(|a, b| a.k.partial_cmp(&b.k).unwrap());
+    surfaces
+}
+
+/// The closest surface at least 1 along the ray
+fn nearest(surfaces: Vec<Surface>, u: Vec3) -> Option<Hit> {
+    surfaces.iter().find(|surface| surface.k >= 1.).map(|surface| surface.hit(u))
+}
+
+/// Spans between the surfaces of a closed shape, which a ray alternately enters and leaves
+fn intervals(surfaces: Vec<Surface>) -> Vec<Interval> {
+    surfaces
+        .chunks_exact(2)
+        .map(|pair| Interval {
+            enter: pair[0],
+            exit: pair[1],
+        })
+        .collect()
+}
+
+impl Cylinder {
+    /// Where a ray crosses the surface, and its unit direction
+    fn surfaces(&self, r: &Ray, t: f64) -> (Vec3, Vec<Surface>) {
+        let (radius, height) = (self.r.at(t), self.height.at(t));
+        let u = (r.q - r.p).norm(); // Unit direction vector
+        let o = r.p - self.pos.at(t);
+
+        // x² + z² = r²
+        let mut candidates = vec![];
+        let a = u.x * u.x + u.z * u.z;
+        let b = 2. * (o.x * u.x + o.z * u.z);
+        let c = o.x * o.x + o.z * o.z - radius * radius;
+        side([a, b, c], o, u, height, |p| Vec3 { x: p.x, y: 0., z: p.z }, &mut candidates);
+        if self.capped {
+            cap(o, u, 0., radius, false, &mut candidates);
+            cap(o, u, height, radius, true, &mut candidates);
+        }
+        (u, surfaces(candidates, r, u, self.color.at(t), self.reflectivity.at(t)))
+    }
+}
+
+impl Renderable for Cylinder {
+    fn intersects(&self, r: &Ray, t: f64) -> Option<Hit> {
+        let (u, surfaces) = self.surfaces(r, t);
+        nearest(surfaces, u)
+    }
+
+    fn intervals(&self, r: &Ray, t: f64) -> Vec<Interval> {
+        if self.capped {
+            intervals(self.surfaces(r, t).1)
+        } else {
+            vec![]
+        }
+    }
+}
+
+impl Cone {
+    /// Where a ray crosses the surface, and its unit direction
+    fn surfaces(&self, r: &Ray, t: f64) -> (Vec3, Vec<Surface>) {
+        let (radius, height) = (self.r.at(t), self.height.at(t));
+        let u = (r.q - r.p).norm(); // Unit direction vector
+        let o = r.p - self.pos.at(t);
+
+        // x² + z² = s² (height - y)², with the slope s
+        let mut candidates = vec![];
+        let s2 = (radius / height).powi(2);
+        let top = height - o.y;
+        let a = u.x * u.x + u.z * u.z - s2 * u.y * u.y;
+        let b = 2. * (o.x * u.x + o.z * u.z + s2 * top * u.y);
+        let c = o.x * o.x + o.z * o.z - s2 * top * top;
+        let gradient = |p: Vec3| Vec3 {
+            x: p.x,
+            y: s2 * (height - p.y),
+            z: p.z,
+        };
+        side([a, b, c], o, u, height, gradient, &mut candidates);
+        if self.capped {
+            cap(o, u, 0., radius, false, &mut candidates);
+        }
+        (u, surfaces(candidates, r, u, self.color.at(t), self.reflectivity.at(t)))
+    }
+}
+
+impl Renderable for Cone {
+    fn intersects(&self, r: &Ray, t: f64) -> Option<Hit> {
+        let (u, surfaces) = self.surfaces(r, t);
+        nearest(surfaces, u)
+    }
+
+    fn intervals(&self, r: &Ray, t: f64) -> Vec<Interval> {
+        if self.capped {
+            intervals(self.surfaces(r, t).1)
+        } else {
+            vec![]
+        }
+    }
+}
+
+impl Paraboloid {
+    /// Where a ray crosses the surface, and its unit direction
+    fn surfaces(&self, r: &Ray, t: f64) -> (Vec3, Vec<Surface>) {
+        let (radius, height) = (self.r.at(t), self.height.at(t));
+        let u = (r.q - r.p).norm(); // Unit direction vector
+        let o = r.p - self.pos.at(t);
+
+        // x² + z² = w y, widening to the radius at the height
+        let mut candidates = vec![];
+        let w = radius * radius / height;
+        let a = u.x * u.x + u.z * u.z;
+        let b = 2. * (o.x * u.x + o.z * u.z) - w * u.y;
+        let c = o.x * o.x + o.z * o.z - w * o.y;
+        let gradient = |p: Vec3| Vec3 {
+            x: 2. * p.x,
+            y: -w,
+            z: 2. * p.z,
+        };
+        side([a, b, c], o, u, height, gradient, &mut candidates);
+        if self.capped {
+            cap(o, u, height, radius, true, &mut candidates);
+        }
+        (u, surfaces(candidates, r, u, self.color.at(t), self.reflectivity.at(t)))
+    }
+}
+
+impl Renderable for Paraboloid {
+    fn intersects(&self, r: &Ray, t: f64) -> Option<Hit> {
+        let (u, surfaces) = self.surfaces(r, t);
+        nearest(surfaces, u)
+    }
+
+    fn intervals(&self, r: &Ray, t: f64) -> Vec<Interval> {
+        if self.capped {
+            intervals(self.surfaces(r, t).1)
+        } else {
+            vec![]
+        }
+    }
+}
+
+impl Fingerprint for Cylinder {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Cylinder { pos, r, height, capped, color, reflectivity } = self;
+        h.tag("cylinder");
+        pos.fingerprint(h);
+        r.fingerprint(h);
+        height.fingerprint(h);
+        capped.fingerprint(h);
+        color.fingerprint(h);
+        reflectivity.fingerprint(h);
+    }
+}
+
+impl Fingerprint for Cone {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Cone { pos, r, height, capped, color, reflectivity } = self;
+        h.tag("cone");
+        pos.fingerprint(h);
+        r.fingerprint(h);
+        height.fingerprint(h);
+        capped.fingerprint(h);
+        color.fingerprint(h);
+        reflectivity.fingerprint(h);
+    }
+}
+
+impl Fingerprint for Paraboloid {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Paraboloid { pos, r, height, capped, color, reflectivity } = self;
+        h.tag("paraboloid");
+        pos.fingerprint(h);
+        r.fingerprint(h);
+        height.fingerprint(h);
+        capped.fingerprint(h);
+        color.fingerprint(h);
+        reflectivity.fingerprint(h);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracer::Rgb;
+
+    fn ray(p: Vec3, dir: Vec3) -> Ray {
+        Ray { p, q: p + dir }
+    }
+
+    fn v(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).mag() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    fn cylinder(capped: bool) -> Cylinder {
+        Cylinder {
+            pos: v(0., 0., 0.).into(),
+            r: Animated::Constant(10.),
+            height: Animated::Constant(20.),
+            capped,
+            color: Color::from(Rgb::WHITE).into(),
+            reflectivity: Animated::Constant(0.),
+        }
+    }
+
+    fn cone(capped: bool) -> Cone {
+        Cone {
+            pos: v(0., 0., 0.).into(),
+            r: Animated::Constant(10.),
+            height: Animated::Constant(10.),
+            capped,
+            color: Color::from(Rgb::WHITE).into(),
+            reflectivity: Animated::Constant(0.),
+        }
+    }
+
+    fn paraboloid(capped: bool) -> Paraboloid {
+        Paraboloid {
+            pos: v(0., 0., 0.).into(),
+            r: Animated::Constant(10.),
+            height: Animated::Constant(10.),
+            capped,
+            color: Color::from(Rgb::WHITE).into(),
+            reflectivity: Animated::Constant(0.),
+        }
+    }
+
+    #[test]
+    fn cylinder_side() {
+        let hit = cylinder(false).intersects(&ray(v(-50., 5., 0.), v(1., 0., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(-10., 5., 0.));
+        assert_close(hit.normal, v(-1., 0., 0.));
+        assert_close(hit.reflection.q - hit.reflection.p, v(-1., 0., 0.));
+        assert!((hit.uv.1 - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cylinder_grazing() {
+        let graze = |x: f64| cylinder(false).intersects(&ray(v(x, 5., -50.), v(0., 0., 1.)), 0.);
+        assert!(graze(10.001).is_none());
+        let hit = graze(9.999).unwrap();
+        assert!(hit.normal.x > 0.99);
+    }
+
+    #[test]
+    fn cylinder_from_inside() {
+        let hit = cylinder(false).intersects(&ray(v(0., 5., 0.), v(0., 0., 1.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(0., 5., 10.));
+        assert_close(hit.normal, v(0., 0., -1.));
+        // Without caps the ray leaves through the open top
+        assert!(cylinder(false).intersects(&ray(v(0., 5., 0.), v(0., 1., 0.)), 0.).is_none());
+        let hit = cylinder(true).intersects(&ray(v(0., 5., 0.), v(0., 1., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(0., 20., 0.));
+        assert_close(hit.normal, v(0., -1., 0.));
+    }
+
+    #[test]
+    fn cylinder_miss() {
+        assert!(cylinder(true).intersects(&ray(v(-50., 25., 0.), v(1., 0., 0.)), 0.).is_none());
+        assert!(cylinder(true).intersects(&ray(v(-50., 5., 0.), v(-1., 0., 0.)), 0.).is_none());
+        assert!(cylinder(true).intersects(&ray(v(0., 50., 0.), v(1., 1., 0.)), 0.).is_none());
+    }
+
+    #[test]
+    fn cylinder_caps() {
+        let hit = cylinder(true).intersects(&ray(v(3., 50., 4.), v(0., -1., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(3., 20., 4.));
+        assert_close(hit.normal, v(0., 1., 0.));
+        // Uncapped the ray goes through to the inside of the bottom
+        assert!(cylinder(false).intersects(&ray(v(3., 50., 4.), v(0., -1., 0.)), 0.).is_none());
+    }
+
+    #[test]
+    fn cone_side() {
+        // Halfway up the radius is halved and the slope is 45°
+        let hit = cone(false).intersects(&ray(v(-50., 5., 0.), v(1., 0., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(-5., 5., 0.));
+        assert_close(hit.normal, v(-1., 1., 0.).norm());
+    }
+
+    #[test]
+    fn cone_grazing() {
+        // Along the slope of the cone, just outside and just inside
+        let graze = |offset: f64| cone(false).intersects(&ray(v(-20. - offset, -10., 0.), v(1., 1., 0.)), 0.);
+        assert!(graze(0.001).is_none());
+        assert!(graze(-0.001).is_some());
+    }
+
+    #[test]
+    fn cone_from_inside() {
+        let hit = cone(true).intersects(&ray(v(0., 2., 0.), v(0., -1., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(0., 0., 0.));
+        assert_close(hit.normal, v(0., 1., 0.));
+        let hit = cone(true).intersects(&ray(v(0., 2., 0.), v(1., 0., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(8., 2., 0.));
+    }
+
+    #[test]
+    fn cone_miss() {
+        // Over the tip, and through the mirrored cone above it
+        assert!(cone(true).intersects(&ray(v(-50., 15., 0.), v(1., 0., 0.)), 0.).is_none());
+        assert!(cone(true).intersects(&ray(v(0., 50., 20.), v(0., 0., -1.)), 0.).is_none());
+    }
+
+    #[test]
+    fn paraboloid_inside_and_outside() {
+        // Down into the bowl onto its lowest point
+        let hit = paraboloid(false).intersects(&ray(v(0., 50., 0.), v(0., -1., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(0., 0., 0.));
+        assert_close(hit.normal, v(0., 1., 0.));
+        // From below onto the outside, where x² = 10 y
+        let hit = paraboloid(false).intersects(&ray(v(5., -50., 0.), v(0., 1., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(5., 2.5, 0.));
+        assert_close(hit.normal, v(10., -10., 0.).norm());
+    }
+
+    #[test]
+    fn paraboloid_from_inside() {
+        let hit = paraboloid(true).intersects(&ray(v(0., 5., 0.), v(0., 1., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(0., 10., 0.));
+        assert!(paraboloid(false).intersects(&ray(v(0., 5., 0.), v(0., 1., 0.)), 0.).is_none());
+    }
+
+    #[test]
+    fn paraboloid_grazing() {
+        // Tangent to x² = 10 y at (5, 2.5) is y = x - 2.5
+        let graze = |offset: f64| paraboloid(false).intersects(&ray(v(-20., -22.5 + offset, 0.), v(1., 1., 0.)), 0.);
+        assert!(graze(0.001).is_some());
+        assert!(graze(-0.001).is_none());
+    }
+
+    #[test]
+    fn paraboloid_miss() {
+        assert!(paraboloid(true).intersects(&ray(v(-50., 15., 0.), v(1., 0., 0.)), 0.).is_none());
+        assert!(paraboloid(true).intersects(&ray(v(-50., 5., 20.), v(1., 0., 0.)), 0.).is_none());
+    }
+}
@@ -0,0 +1,52 @@
+use std::f64::consts::PI;
+
+/// Small xorshift generator, seeded per pixel so renders are reproducible
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        // Splitmix scrambles neighbouring seeds, and xorshift must not start at 0
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        Random {
+            state: (z ^ (z >> 31)) | 1,
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        // xorshift64*
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in 0..1
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform point in the unit disk
+    pub fn disk(&mut self) -> (f64, f64) {
+        let r = self.next_f64().sqrt();
+        let (sin, cos) = (2. * PI * self.next_f64()).sin_cos();
+        (r * cos, r * sin)
+    }
+
+    /// Uniform point in a regular polygon with its corners on the unit circle
+    pub fn polygon(&mut self, corners: u32) -> (f64, f64) {
+        // Pick one of the triangles between the centre and two neighbouring corners
+        let corner = (self.next_f64() * corners as f64) as u32;
+        let angle = |i: u32| 2. * PI * i as f64 / corners as f64;
+        let (a, b) = (angle(corner), angle(corner + 1));
+        let (mut u, mut v) = (self.next_f64(), self.next_f64());
+        if u + v > 1. {
+            u = 1. - u;
+            v = 1. - v;
+        }
+        (u * a.cos() + v * b.cos(), u * a.sin() + v * b.sin())
+    }
+}
@@ -1,5 +1,12 @@
-use super::{Ray, hit::Hit};
+use super::{Ray, fingerprint::Fingerprint, hit::{Hit, Interval}};
 
-pub(in super) trait Renderable {
+pub(in super) trait Renderable: std::fmt::Debug + Fingerprint {
     fn intersects(&self, l: &Ray, t: f64) -> Option<Hit>;
-}
\ No newline at end of file
+
+    /// Spans of the ray inside the object, in order along the ray, including those behind its start
+    ///
+    /// Only closed solids have an inside, everything else is empty when combined with `Csg`.
+    fn intervals(&self, _l: &Ray, _t: f64) -> Vec<Interval> {
+        vec![]
+    }
+}
@@ -0,0 +1,125 @@
+//! Real roots of polynomials up to the fourth degree, as found by ray-surface intersections
+//!
+//! Roots that touch without crossing, like a ray grazing a surface, are not
+//! reported, so a tangent ray misses as it does for spheres.
+
+use std::f64::consts::PI;
+
+/// Roots of `a x² + b x + c`, ascending
+pub fn quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        return if b.abs() < 1e-12 { vec![] } else { vec![-c / b] };
+    }
+    let discriminant = b * b - 4. * a * c;
+    if discriminant <= 0. {
+        return vec![];
+    }
+    // Avoids subtracting nearly equal numbers
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (x1, x2) = (q / a, c / q);
+    vec![x1.min(x2), x1.max(x2)]
+}
+
+/// Roots of `x³ + a x² + b x + c`, ascending, including double ones
+fn cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    // https://en.wikipedia.org/wiki/Cubic_equation#Trigonometric_and_hyperbolic_solutions
+    let shift = a / 3.;
+    let p = b - a * a / 3.;
+    let q = 2. * a * a * a / 27. - a * b / 3. + c;
+    let mut roots = if p.abs() < 1e-12 {
+        vec![(-q).cbrt()]
+    } else {
+        let discriminant = q * q / 4. + p * p * p / 27.;
+        if discriminant > 0. {
+            // One real root
+            let s = discriminant.sqrt();
+            vec![(-q / 2. + s).cbrt() + (-q / 2. - s).cbrt()]
+        } else {
+            // Three real roots
+            let m = 2. * (-p / 3.).sqrt();
+            let angle = (3. * q / (p * m)).clamp(-1., 1.).acos() / 3.;
+            (0..3).map(|i| m * (angle - 2. * PI * i as f64 / 3.).cos()).collect()
+        }
+    };
+    for root in roots.iter_mut() {
+        *root -= shift;
+    }
+    roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    roots
+}
+
+/// Roots of `x⁴ + a x³ + b x² + c x + d`, ascending
+pub fn quartic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    // https://en.wikipedia.org/wiki/Quartic_function#Ferrari's_solution
+    // Depressed to y⁴ + p y² + q y + r with x = y - a / 4
+    let shift = a / 4.;
+    let p = b - 3. * a * a / 8.;
+    let q = c - a * b / 2. + a * a * a / 8.;
+    let r = d - a * c / 4. + a * a * b / 16. - 3. * a * a * a * a / 256.;
+
+    let mut roots = if q.abs() < 1e-12 {
+        // Biquadratic, a quadratic in y²
+        quadratic(1., p, r)
+            .into_iter()
+            .filter(|y2| *y2 > 0.)
+            .flat_map(|y2| vec![-y2.sqrt(), y2.sqrt()])
+            .collect()
+    } else {
+        // Splits into two quadratics with the largest root of the resolvent cubic, which is positive
+        let m = *cubic(p, p * p / 4. - r, -q * q / 8.).last().unwrap();
+        if m <= 0. {
+            return vec![];
+        }
+        let s = (2. * m).sqrt();
+        let mut roots = quadratic(1., -s, p / 2. + m + q / (2. * s));
+        roots.extend(quadratic(1., s, p / 2. + m - q / (2. * s)));
+        roots
+    };
+
+    // Polishes the roots against the original polynomial
+    for root in roots.iter_mut() {
+        let mut x = *root - shift;
+        for _ in 0..2 {
+            let f = (((x + a) * x + b) * x + c) * x + d;
+            let df = ((4. * x + 3. * a) * x + 2. * b) * x + c;
+            if df.abs() > 1e-12 {
+                x -= f / df;
+            }
+        }
+        *root = x;
+    }
+    roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(roots: Vec<f64>, expected: &[f64]) {
+        assert_eq!(roots.len(), expected.len(), "{:?} != {:?}", roots, expected);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-9, "{:?} != {:?}", roots, expected);
+        }
+    }
+
+    #[test]
+    fn quadratic_roots() {
+        assert_roots(quadratic(2., -2., -4.), &[-1., 2.]);
+        assert_roots(quadratic(0., 2., -4.), &[2.]);
+        assert_roots(quadratic(1., -2., 1.), &[]);
+        assert_roots(quadratic(1., 0., 1.), &[]);
+    }
+
+    #[test]
+    fn quartic_roots() {
+        // (x + 2)(x - 1)(x - 3)(x - 5)
+        assert_roots(quartic(-7., 5., 31., -30.), &[-2., 1., 3., 5.]);
+        // (x² - 1)(x² - 4)
+        assert_roots(quartic(0., -5., 0., 4.), &[-2., -1., 1., 2.]);
+        // (x² + 1)(x - 1)(x - 2)
+        assert_roots(quartic(-3., 3., -3., 2.), &[1., 2.]);
+        // (x² + 1)(x² + 2)
+        assert_roots(quartic(0., 3., 0., 2.), &[]);
+    }
+}
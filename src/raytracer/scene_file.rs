@@ -0,0 +1,239 @@
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::SplitWhitespace;
+
+use super::animated::{Animated, Components, Interpolate, Interpolation, Keyframes};
+use super::sphere::Sphere;
+use super::vec3::Vec3;
+use super::{Color, Rgb};
+
+/// Animated additions to the built-in scene
+///
+/// Scene files are text with one setting per line:
+///
+/// ```text
+/// # Comments start with a hash
+/// sky keys linear loop
+/// key 0 0.26 0.26 0.26
+/// key 3 0.8 0.45 0.3
+/// key 6 0.26 0.26 0.26
+///
+/// sphere
+/// pos 0 -80 130
+/// r keys spline loop
+/// key 0 10
+/// key 1 20
+/// key 2 10
+/// color = 0.5 + 0.5 * sin(t), 0, 0
+/// ```
+///
+/// `sky` sets the background colour. `sphere` adds a sphere, the lines after
+/// it set its `pos`, `r`, `color` and `reflectivity`. Values are numbers, `=`
+/// followed by a formula of the time `t`, or `keys` with an interpolation of
+/// `step`, `linear`, `spline`, `ease-in`, `ease-out` or `ease-in-out` and
+/// optionally `loop`, followed by `key` lines with a time and the numbers.
+#[derive(Clone, Debug)]
+pub struct SceneFile {
+    pub(super) sky: Option<Animated<Color>>,
+    pub(super) spheres: Vec<Sphere>,
+}
+
+impl SceneFile {
+    pub fn load(path: &Path) -> Result<SceneFile, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        SceneFile::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<SceneFile, String> {
+        let mut scene_file = SceneFile {
+            sky: None,
+            spheres: vec![],
+        };
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(number, line)| (number + 1, line.split('#').next().unwrap()))
+            .filter(|(_, line)| !line.trim().is_empty())
+            .peekable();
+
+        while let Some((number, line)) = lines.next() {
+            let mut words = line.split_whitespace();
+            let word = words.next().unwrap();
+            match word {
+                "sky" => scene_file.sky = Some(value(number, words, &mut lines)?),
+                "sphere" => scene_file.spheres.push(Sphere {
+                    pos: Vec3::NULL.into(),
+                    r: Animated::Constant(10.),
+                    color: Color::from(Rgb::WHITE).into(),
+                    reflectivity: Animated::Constant(0.),
+                }),
+                "pos" | "r" | "color" | "reflectivity" => {
+                    let sphere = match scene_file.spheres.last_mut() {
+                        Some(sphere) => sphere,
+                        None => return Err(format!("line {}: {} comes before any sphere", number, word)),
+                    };
+                    match word {
+                        "pos" => sphere.pos = value(number, words, &mut lines)?,
+                        "r" => sphere.r = value(number, words, &mut lines)?,
+                        "color" => sphere.color = value(number, words, &mut lines)?,
+                        _ => sphere.reflectivity = value(number, words, &mut lines)?,
+                    }
+                }
+                _ => return Err(format!("line {}: unknown keyword {}", number, word)),
+            }
+        }
+        Ok(scene_file)
+    }
+}
+
+/// A constant, a formula, or keyframes with their keys on the following lines
+fn value<'a, T, I>(number: usize, mut words: SplitWhitespace, lines: &mut Peekable<I>) -> Result<Animated<T>, String>
+where
+    T: Interpolate + Components,
+    I: Iterator<Item = (usize, &'a str)>,
+{
+    let error = |number: usize, message: String| format!("line {}: {}", number, message);
+    match words.next() {
+        Some("keys") => (),
+        // The caret of formula errors lines up with the formula on its own line
+        Some("=") => {
+            let source = words.collect::<Vec<&str>>().join(" ");
+            return Animated::formula(&source).map_err(|e| format!("line {}:\n{}", number, e));
+        }
+        first => {
+            let words: Vec<&str> = first.into_iter().chain(words).collect();
+            return numbers(&words).map(Animated::Constant).map_err(|e| error(number, e));
+        }
+    }
+
+    let interpolation = match words.next().and_then(Interpolation::parse) {
+        Some(interpolation) => interpolation,
+        None => {
+            let message = "keys need step, linear, spline, ease-in, ease-out or ease-in-out".to_string();
+            return Err(error(number, message));
+        }
+    };
+    let looping = match words.next() {
+        None => false,
+        Some("loop") => true,
+        Some(word) => return Err(error(number, format!("expected loop, not {}", word))),
+    };
+    let mut keys = vec![];
+    while let Some((number, line)) = lines.next_if(|(_, line)| line.split_whitespace().next() == Some("key")) {
+        let words: Vec<&str> = line.split_whitespace().skip(1).collect();
+        let (time, values) = match words.split_first() {
+            Some(key) => key,
+            None => return Err(error(number, "key needs a time".to_string())),
+        };
+        let time = time.parse().map_err(|_| error(number, format!("{} is not a time", time)))?;
+        keys.push((time, numbers(values).map_err(|e| error(number, e))?));
+    }
+    Keyframes::new(keys, interpolation, looping)
+        .map(Animated::Keyframes)
+        .map_err(|e| error(number, e))
+}
+
+/// Exactly as many numbers as the value has components
+fn numbers<T: Components>(words: &[&str]) -> Result<T, String> {
+    if words.len() != T::COUNT {
+        return Err(format!("expected {} numbers, not {}", T::COUNT, words.len()));
+    }
+    let values = words
+        .iter()
+        .map(|word| word.parse().map_err(|_| format!("{} is not a number", word)))
+        .collect::<Result<Vec<f64>, String>>()?;
+    Ok(T::from_components(&values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn error(text: &str) -> String {
+        SceneFile::parse(text).unwrap_err()
+    }
+
+    #[test]
+    fn spheres() {
+        let scene_file = SceneFile::parse(
+            "# Two spheres\n\
+             sphere\n\
+             pos 1 2 3 # Trailing comment\n\
+             \n\
+             sphere\n\
+             r keys linear\n\
+             key 0 10\n\
+             key 2 20\n\
+             color keys step loop\n\
+             key 0 1 0 0\n\
+             key 1 0 0 1\n\
+             key 2 1 0 0\n\
+             reflectivity 0.5\n",
+        )
+        .unwrap();
+        assert!(scene_file.sky.is_none());
+        let [first, second] = &scene_file.spheres[..] else {
+            panic!("expected two spheres");
+        };
+        let pos = first.pos.at(0.);
+        assert!(close(pos.x, 1.) && close(pos.y, 2.) && close(pos.z, 3.));
+        assert!(close(first.r.at(0.), 10.));
+        assert!(close(second.r.at(1.), 15.) && close(second.r.at(5.), 20.));
+        assert!(close(second.color.at(1.5).b, 1.) && close(second.color.at(2.5).r, 1.));
+        assert!(close(second.reflectivity.at(0.), 0.5));
+    }
+
+    #[test]
+    fn sky() {
+        let scene_file = SceneFile::parse("sky keys ease-in-out\nkey 0 0 0 0\nkey 1 1 0.5 0\n").unwrap();
+        let sky = scene_file.sky.unwrap();
+        assert!(close(sky.at(0.5).r, 0.5) && close(sky.at(0.5).g, 0.25));
+        assert!(sky.at(0.25).r < 0.25);
+    }
+
+    #[test]
+    fn formulas() {
+        let scene_file = SceneFile::parse("sphere\npos = r = 40; r * sin(t), -40, r * cos(t)\nr = 5 + t").unwrap();
+        let sphere = &scene_file.spheres[0];
+        let pos = sphere.pos.at(PI / 2.);
+        assert!(close(pos.x, 40.) && close(pos.y, -40.) && close(pos.z, 0.));
+        assert!(close(sphere.r.at(2.), 7.));
+        assert_eq!(
+            error("sphere\nr = 2 * t, 3"),
+            "line 2:\n2 * t, 3\n       ^\ncolumn 8: expected 1 comma separated values, not 2"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("pos 0 0 0"), "line 1: pos comes before any sphere");
+        assert_eq!(error("sphere\nr 1 2"), "line 2: expected 1 numbers, not 2");
+        assert_eq!(error("sphere\npos 0 x 0"), "line 2: x is not a number");
+        assert_eq!(error("sphere\nsize 2"), "line 2: unknown keyword size");
+        assert_eq!(
+            error("sky keys cubic\nkey 0 0 0 0"),
+            "line 1: keys need step, linear, spline, ease-in, ease-out or ease-in-out"
+        );
+        assert_eq!(error("sky keys linear forever"), "line 1: expected loop, not forever");
+        assert_eq!(error("sky keys linear"), "line 1: keyframes need at least one key");
+        assert_eq!(error("sky keys linear\nkey 0 0 0 0\n\nkey 0 1 1 1"), "line 1: key 2 has to come after key 1");
+        assert_eq!(error("sky keys linear\nkey\n"), "line 2: key needs a time");
+        assert_eq!(error("sky keys linear\nkey 0 0 0\n"), "line 2: expected 3 numbers, not 2");
+    }
+
+    #[test]
+    fn examples() {
+        let text = std::fs::read_to_string("scenes/dusk.scene").unwrap();
+        assert!(SceneFile::parse(&text).is_ok());
+    }
+
+    #[test]
+    fn missing_file() {
+        assert!(SceneFile::load(Path::new("scenes/missing.scene")).is_err());
+    }
+}
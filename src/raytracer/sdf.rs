@@ -0,0 +1,352 @@
+use super::{
+    animated::Animated,
+    fingerprint::{Fingerprint, Fnv},
+    hit::Hit,
+    renderable::Renderable,
+    roots,
+    sphere::Sphere,
+    vec3::Vec3,
+    Color, Ray,
+};
+
+/// Steps before a ray gives up, grazing rays crawl along surfaces
+const MAX_STEPS: u32 = 256;
+/// Length of rays without a bound
+const MAX_DISTANCE: f64 = 2000.;
+/// Distance at which a ray counts as touching the surface
+const EPSILON: f64 = 0.01;
+
+/// A shape given by the distance to its surface, negative inside
+///
+/// Primitives are centred on the origin, with tori and twists around the y axis.
+/// See https://iquilezles.org/articles/distfunctions/
+#[derive(Debug)]
+pub enum Sdf {
+    Sphere(f64),
+    /// Half the size along each axis
+    Box(Vec3),
+    /// Box with edges rounded by a radius, within the same size
+    RoundBox(Vec3, f64),
+    /// Distance from the centre to the middle of the tube and radius of the tube
+    Torus(f64, f64),
+    /// Line between two points, thickened by a radius
+    Capsule(Vec3, Vec3, f64),
+    Translate(Vec3, Box<Sdf>),
+    /// Union with a blend over the distance given last
+    SmoothUnion(Box<Sdf>, Box<Sdf>, f64),
+    /// The first without the second, with a blend over the distance given last
+    SmoothSubtraction(Box<Sdf>, Box<Sdf>, f64),
+    /// Repeats the shape in cells of the given size, 0 doesn't repeat along that axis
+    Repeat(Vec3, Box<Sdf>),
+    /// Turns the shape further around the y axis the higher up, by radians per unit
+    Twist(f64, Box<Sdf>),
+}
+
+fn abs(v: Vec3) -> Vec3 {
+    Vec3 {
+        x: v.x.abs(),
+        y: v.y.abs(),
+        z: v.z.abs(),
+    }
+}
+
+fn positive(v: Vec3) -> Vec3 {
+    Vec3 {
+        x: v.x.max(0.),
+        y: v.y.max(0.),
+        z: v.z.max(0.),
+    }
+}
+
+fn mix(a: f64, b: f64, h: f64) -> f64 {
+    a + (b - a) * h
+}
+
+/// Position within the cell around the origin
+fn repeat(x: f64, size: f64) -> f64 {
+    if size == 0. {
+        x
+    } else {
+        x - size * (x / size).round()
+    }
+}
+
+impl Sdf {
+    pub fn distance(&self, p: Vec3) -> f64 {
+        match self {
+            Sdf::Sphere(r) => p.mag() - r,
+            Sdf::Box(size) => {
+                let q = abs(p) - *size;
+                positive(q).mag() + q.x.max(q.y).max(q.z).min(0.)
+            }
+            Sdf::RoundBox(size, r) => Sdf::Box(*size - Vec3 { x: *r, y: *r, z: *r }).distance(p) - r,
+            Sdf::Torus(major, minor) => {
+                let around = (p.x * p.x + p.z * p.z).sqrt() - major;
+                (around * around + p.y * p.y).sqrt() - minor
+            }
+            Sdf::Capsule(a, b, r) => {
+                let (pa, ba) = (p - *a, *b - *a);
+                let h = (pa.dot(ba) / ba.dot(ba)).clamp(0., 1.);
+                (pa - ba * h).mag() - r
+            }
+            Sdf::Translate(offset, sdf) => sdf.distance(p - *offset),
+            Sdf::SmoothUnion(a, b, k) => {
+                let (a, b) = (a.distance(p), b.distance(p));
+                let h = (0.5 + 0.5 * (b - a) / k).clamp(0., 1.);
+                mix(b, a, h) - k * h * (1. - h)
+            }
+            Sdf::SmoothSubtraction(a, b, k) => {
+                let (a, b) = (a.distance(p), b.distance(p));
+                let h = (0.5 - 0.5 * (a + b) / k).clamp(0., 1.);
+                mix(a, -b, h) + k * h * (1. - h)
+            }
+            Sdf::Repeat(size, sdf) => sdf.distance(Vec3 {
+                x: repeat(p.x, size.x),
+                y: repeat(p.y, size.y),
+                z: repeat(p.z, size.z),
+            }),
+            Sdf::Twist(rate, sdf) => {
+                let q = p.rotate(Vec3::UP, -rate * p.y);
+                // Twisting stretches space, so the distance is underestimated to not step through the surface
+                sdf.distance(q) * 0.5
+            }
+        }
+    }
+
+    /// Unit vector away from the surface, from central differences
+    fn normal(&self, p: Vec3) -> Vec3 {
+        let e = EPSILON;
+        let d = |x: f64, y: f64, z: f64| self.distance(p + Vec3 { x, y, z }) - self.distance(p - Vec3 { x, y, z });
+        Vec3 {
+            x: d(e, 0., 0.),
+            y: d(0., e, 0.),
+            z: d(0., 0., e),
+        }
+        .norm()
+    }
+}
+
+impl Fingerprint for Sdf {
+    fn fingerprint(&self, h: &mut Fnv) {
+        match self {
+            Sdf::Sphere(r) => {
+                h.tag("sphere");
+                r.fingerprint(h);
+            }
+            Sdf::Box(size) => {
+                h.tag("box");
+                size.fingerprint(h);
+            }
+            Sdf::RoundBox(size, r) => {
+                h.tag("round box");
+                size.fingerprint(h);
+                r.fingerprint(h);
+            }
+            Sdf::Torus(major, minor) => {
+                h.tag("torus");
+                major.fingerprint(h);
+                minor.fingerprint(h);
+            }
+            Sdf::Capsule(a, b, r) => {
+                h.tag("capsule");
+                a.fingerprint(h);
+                b.fingerprint(h);
+                r.fingerprint(h);
+            }
+            Sdf::Translate(offset, sdf) => {
+                h.tag("translate");
+                offset.fingerprint(h);
+                sdf.fingerprint(h);
+            }
+            Sdf::SmoothUnion(a, b, k) => {
+                h.tag("smooth union");
+                a.fingerprint(h);
+                b.fingerprint(h);
+                k.fingerprint(h);
+            }
+            Sdf::SmoothSubtraction(a, b, k) => {
+                h.tag("smooth subtraction");
+                a.fingerprint(h);
+                b.fingerprint(h);
+                k.fingerprint(h);
+            }
+            Sdf::Repeat(size, sdf) => {
+                h.tag("repeat");
+                size.fingerprint(h);
+                sdf.fingerprint(h);
+            }
+            Sdf::Twist(rate, sdf) => {
+                h.tag("twist");
+                rate.fingerprint(h);
+                sdf.fingerprint(h);
+            }
+        }
+    }
+}
+
+/// Signed distance field placed in the scene, rendered by sphere tracing
+///
+/// Rays step forward by the distance to the closest surface until they touch one.
+#[derive(Debug)]
+pub struct DistanceField {
+    pub pos: Animated<Vec3>,
+    pub sdf: Sdf,
+    /// Radius around pos that contains the whole shape, rays outside of it are skipped
+    pub bound: Option<f64>,
+    pub(super) color: Animated<Color>,
+    pub reflectivity: Animated<f64>,
+}
+
+impl Renderable for DistanceField {
+    fn intersects(&self, r: &Ray, t: f64) -> Option<Hit> {
+        let u = (r.q - r.p).norm(); // Unit direction vector
+        let o = r.p - self.pos.at(t);
+
+        // Only marches through the bounding sphere
+        let (mut k, end) = match self.bound {
+            Some(bound) => match roots::quadratic(1., 2. * u.dot(o), o.dot(o) - bound * bound)[..] {
+                [enter, exit] => (enter.max(1.), exit),
+                _ => return None,
+            },
+            None => (1., MAX_DISTANCE),
+        };
+        for _ in 0..MAX_STEPS {
+            if k > end {
+                return None;
+            }
+            let p = o + u * k;
+            // Rays starting inside march out through the surface the same way
+            let d = self.sdf.distance(p).abs();
+            if d < EPSILON {
+                let n = self.sdf.normal(p);
+                let n = if n.dot(u) > 0. { -n } else { n };
+                let uv = Sphere::uv(n);
+                return Some(Hit::reflect(u, r.p + u * k, n, self.color.at(t), self.reflectivity.at(t), uv));
+            }
+            k += d;
+        }
+        None
+    }
+}
+
+impl Fingerprint for DistanceField {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let DistanceField { pos, sdf, bound, color, reflectivity } = self;
+        h.tag("distance field");
+        pos.fingerprint(h);
+        sdf.fingerprint(h);
+        bound.fingerprint(h);
+        color.fingerprint(h);
+        reflectivity.fingerprint(h);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracer::Rgb;
+
+    fn v(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn ray(p: Vec3, dir: Vec3) -> Ray {
+        Ray { p, q: p + dir }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).mag() < 2. * EPSILON, "{:?} != {:?}", a, b);
+    }
+
+    fn sphere(r: f64) -> Box<Sdf> {
+        Box::new(Sdf::Sphere(r))
+    }
+
+    fn field(sdf: Sdf, bound: Option<f64>) -> DistanceField {
+        DistanceField {
+            pos: Vec3::NULL.into(),
+            sdf,
+            bound,
+            color: Color::from(Rgb::WHITE).into(),
+            reflectivity: Animated::Constant(0.),
+        }
+    }
+
+    #[test]
+    fn distances() {
+        assert!(close(Sdf::Sphere(10.).distance(v(0., 20., 0.)), 10.));
+        assert!(close(Sdf::Sphere(10.).distance(Vec3::NULL), -10.));
+
+        let cube = Sdf::Box(v(10., 10., 10.));
+        assert!(close(cube.distance(v(15., 0., 0.)), 5.));
+        assert!(close(cube.distance(v(13., 14., 10.)), 5.));
+        assert!(close(cube.distance(v(8., 0., 0.)), -2.));
+        // Rounding pulls the corner in but keeps the faces
+        let rounded = Sdf::RoundBox(v(10., 10., 10.), 2.);
+        assert!(close(rounded.distance(v(15., 0., 0.)), 5.));
+        assert!(rounded.distance(v(10., 10., 10.)) > 0.);
+
+        let torus = Sdf::Torus(20., 5.);
+        assert!(close(torus.distance(v(0., 0., 20.)), -5.));
+        assert!(close(torus.distance(Vec3::NULL), 15.));
+        let capsule = Sdf::Capsule(v(-10., 0., 0.), v(10., 0., 0.), 2.);
+        assert!(close(capsule.distance(v(0., 5., 0.)), 3.));
+        assert!(close(capsule.distance(v(15., 0., 0.)), 3.));
+        assert!(close(Sdf::Translate(v(5., 0., 0.), sphere(1.)).distance(v(5., 3., 0.)), 2.));
+    }
+
+    #[test]
+    fn combinations() {
+        let pair = |d: f64| Sdf::SmoothUnion(sphere(1.), Box::new(Sdf::Translate(v(d, 0., 0.), sphere(1.))), 2.);
+        // Far apart the union is the closer shape, close by it bulges out between them
+        assert!(close(pair(100.).distance(v(-3., 0., 0.)), 2.));
+        assert!(pair(3.).distance(v(1.5, 0., 0.)) < 0.5);
+
+        let carved = Sdf::SmoothSubtraction(sphere(10.), Box::new(Sdf::Translate(v(10., 0., 0.), sphere(5.))), 0.1);
+        assert!(carved.distance(v(9., 0., 0.)) > 0.);
+        assert!(close(carved.distance(v(-12., 0., 0.)), 2.));
+
+        let repeated = Sdf::Repeat(v(10., 0., 0.), sphere(1.));
+        assert!(close(repeated.distance(v(30., 0., 0.)), -1.));
+        assert!(close(repeated.distance(v(35., 3., 0.)), (25f64 + 9.).sqrt() - 1.));
+        assert!(close(repeated.distance(v(0., 30., 0.)), 29.));
+        // Twisting a shape that is round around y only halves the distance
+        assert!(close(Sdf::Twist(0.5, sphere(1.)).distance(v(0., 3., 0.)), 1.));
+    }
+
+    #[test]
+    fn sphere_tracing() {
+        let ball = field(Sdf::Sphere(10.), None);
+        let hit = ball.intersects(&ray(v(-50., 0., 0.), v(1., 0., 0.)), 0.).unwrap();
+        assert_near(hit.reflection.p, v(-10., 0., 0.));
+        assert_near(hit.normal, v(-1., 0., 0.));
+        assert!(ball.intersects(&ray(v(-50., 20., 0.), v(1., 0., 0.)), 0.).is_none());
+        assert!(ball.intersects(&ray(v(-50., 0., 0.), v(-1., 0., 0.)), 0.).is_none());
+    }
+
+    #[test]
+    fn bound_clips_the_shape() {
+        let bounded = field(Sdf::Sphere(10.), Some(15.));
+        let hit = bounded.intersects(&ray(v(-50., 0., 0.), v(1., 0., 0.)), 0.).unwrap();
+        assert_near(hit.reflection.p, v(-10., 0., 0.));
+        assert!(bounded.intersects(&ray(v(-50., 20., 0.), v(1., 0., 0.)), 0.).is_none());
+        // A bound that is too small hides everything outside it
+        let clipped = field(Sdf::Sphere(10.), Some(5.));
+        assert!(clipped.intersects(&ray(v(-50., 0., 0.), v(1., 0., 0.)), 0.).is_none());
+    }
+
+    #[test]
+    fn rays_from_inside() {
+        for bound in [None, Some(15.)] {
+            let ball = field(Sdf::Sphere(10.), bound);
+            let hit = ball.intersects(&ray(Vec3::NULL, v(1., 0., 0.)), 0.).unwrap();
+            assert_near(hit.reflection.p, v(10., 0., 0.));
+            // Facing back into the sphere, at the ray
+            assert_near(hit.normal, v(-1., 0., 0.));
+        }
+    }
+}
@@ -1,18 +1,21 @@
-use super::{hit::Hit, renderable::Renderable, vec3::Vec3, Ray, Rgb};
+use std::f64::consts::PI;
 
-pub struct MovingSphere
-{
-    pub pos: fn(f64) -> Vec3,
-    pub r: f64,
-    pub(super) color: Rgb,
-    pub reflectivity: f64,
-}
+use super::{
+    animated::Animated,
+    fingerprint::{Fingerprint, Fnv},
+    hit::{Hit, Interval, Surface},
+    renderable::Renderable,
+    roots,
+    vec3::Vec3,
+    Color, Ray,
+};
 
+#[derive(Clone, Debug)]
 pub struct Sphere {
-    pub pos: Vec3,
-    pub r: f64,
-    pub(super) color: Rgb,
-    pub reflectivity: f64,
+    pub pos: Animated<Vec3>,
+    pub r: Animated<f64>,
+    pub(super) color: Animated<Color>,
+    pub reflectivity: Animated<f64>,
 }
 
 impl Sphere {
@@ -24,7 +27,7 @@ impl Sphere {
         pos: Vec3,
         radius: f64,
         ray: &Ray,
-        color: Rgb,
+        color: Color,
         reflectivity: f64,
     ) -> Option<Hit> {
         // https://en.wikipedia.org/wiki/Line%E2%80%93sphere_intersection
@@ -58,18 +61,55 @@ impl Sphere {
             },
             color,
             reflectivity,
+            normal: n,
+            uv: Sphere::uv(n),
         })
     }
-}
 
-impl Renderable for MovingSphere {
-    fn intersects(&self, l: &Ray, t: f64) -> Option<Hit> {
-        Sphere::intersects((self.pos)(t), self.r, l, self.color, self.reflectivity)
+    /// Longitude and latitude of a point with the normal `n`
+    pub(super) fn uv(n: Vec3) -> (f64, f64) {
+        (0.5 + n.z.atan2(n.x) / (2. * PI), 0.5 + n.y.asin() / PI)
     }
 }
 
 impl Renderable for Sphere {
-    fn intersects(&self, r: &Ray, _t: f64) -> Option<Hit> {
-        Sphere::intersects(self.pos, self.r, r, self.color, self.reflectivity)
+    fn intersects(&self, r: &Ray, t: f64) -> Option<Hit> {
+        Sphere::intersects(self.pos.at(t), self.r.at(t), r, self.color.at(t), self.reflectivity.at(t))
+    }
+
+    fn intervals(&self, r: &Ray, t: f64) -> Vec<Interval> {
+        let (pos, radius) = (self.pos.at(t), self.r.at(t));
+        let u = (r.q - r.p).norm(); // Unit direction vector
+        let o = r.p - pos;
+        let surface = |k: f64| {
+            let p = r.p + u * k;
+            let normal = (p - pos).norm();
+            Surface {
+                k,
+                p,
+                normal,
+                uv: Sphere::uv(normal),
+                color: self.color.at(t),
+                reflectivity: self.reflectivity.at(t),
+            }
+        };
+        match roots::quadratic(1., 2. * u.dot(o), o.dot(o) - radius * radius)[..] {
+            [enter, exit] => vec![Interval {
+                enter: surface(enter),
+                exit: surface(exit),
+            }],
+            _ => vec![],
+        }
+    }
+}
+
+impl Fingerprint for Sphere {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Sphere { pos, r, color, reflectivity } = self;
+        h.tag("sphere");
+        pos.fingerprint(h);
+        r.fingerprint(h);
+        color.fingerprint(h);
+        reflectivity.fingerprint(h);
     }
 }
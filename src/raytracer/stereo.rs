@@ -0,0 +1,114 @@
+use super::fingerprint::{Fingerprint, Fnv};
+
+/// How the images of both eyes are combined into one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    /// Left eye on the left, twice as wide
+    SideBySide,
+    /// Left eye on top, twice as high
+    TopBottom,
+    /// Red from the left eye, green and blue from the right one, for red/cyan glasses
+    Anaglyph,
+}
+
+impl Layout {
+    pub fn parse(name: &str) -> Option<Layout> {
+        match name {
+            "side-by-side" => Some(Layout::SideBySide),
+            "top-bottom" => Some(Layout::TopBottom),
+            "anaglyph" => Some(Layout::Anaglyph),
+            _ => None,
+        }
+    }
+}
+
+/// Renders an image for each eye
+#[derive(Clone, Copy, Debug)]
+pub struct Stereo {
+    pub layout: Layout,
+    /// Interpupillary distance, how far apart the eyes are
+    pub ipd: f64,
+    /// Distance at which both eyes see the same point in the same place
+    pub convergence: f64,
+}
+
+impl Stereo {
+    /// Combines two rgb float images of the same size
+    pub fn compose(&self, left: &[f32], right: &[f32], width: u16, height: u16) -> (Vec<f32>, u16, u16) {
+        let row = 3 * width as usize;
+        match self.layout {
+            Layout::SideBySide => {
+                let mut pixels = Vec::with_capacity(left.len() * 2);
+                for (l, r) in left.chunks(row).zip(right.chunks(row)) {
+                    pixels.extend_from_slice(l);
+                    pixels.extend_from_slice(r);
+                }
+                (pixels, width * 2, height)
+            }
+            Layout::TopBottom => ([left, right].concat(), width, height * 2),
+            Layout::Anaglyph => {
+                let mut pixels = right.to_vec();
+                for (p, l) in pixels.chunks_mut(3).zip(left.chunks(3)) {
+                    p[0] = l[0];
+                }
+                (pixels, width, height)
+            }
+        }
+    }
+}
+
+impl Fingerprint for Stereo {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Stereo { layout, ipd, convergence } = self;
+        h.tag(match layout {
+            Layout::SideBySide => "side-by-side",
+            Layout::TopBottom => "top-bottom",
+            Layout::Anaglyph => "anaglyph",
+        });
+        ipd.fingerprint(h);
+        convergence.fingerprint(h);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two pixels side by side for each eye
+    const LEFT: [f32; 6] = [1., 2., 3., 4., 5., 6.];
+    const RIGHT: [f32; 6] = [7., 8., 9., 10., 11., 12.];
+
+    fn compose(layout: Layout, width: u16, height: u16) -> (Vec<f32>, u16, u16) {
+        let stereo = Stereo {
+            layout,
+            ipd: 6.4,
+            convergence: 200.,
+        };
+        stereo.compose(&LEFT, &RIGHT, width, height)
+    }
+
+    #[test]
+    fn side_by_side() {
+        let (pixels, width, height) = compose(Layout::SideBySide, 2, 1);
+        assert_eq!((width, height), (4, 1));
+        assert_eq!(pixels, [&LEFT[..], &RIGHT[..]].concat());
+        // Every row continues with the right eye
+        let (pixels, width, height) = compose(Layout::SideBySide, 1, 2);
+        assert_eq!((width, height), (2, 2));
+        assert_eq!(pixels, [1., 2., 3., 7., 8., 9., 4., 5., 6., 10., 11., 12.]);
+    }
+
+    #[test]
+    fn top_bottom() {
+        let (pixels, width, height) = compose(Layout::TopBottom, 2, 1);
+        assert_eq!((width, height), (2, 2));
+        assert_eq!(pixels, [&LEFT[..], &RIGHT[..]].concat());
+    }
+
+    #[test]
+    fn anaglyph() {
+        let (pixels, width, height) = compose(Layout::Anaglyph, 2, 1);
+        assert_eq!((width, height), (2, 1));
+        assert_eq!(pixels, [1., 8., 9., 4., 11., 12.]);
+    }
+}
@@ -0,0 +1,162 @@
+use std::f64::consts::PI;
+
+use super::{
+    animated::Animated,
+    fingerprint::{Fingerprint, Fnv},
+    hit::{Hit, Interval, Surface},
+    renderable::Renderable,
+    roots,
+    vec3::Vec3,
+    Color, Ray,
+};
+
+/// Ring around the y axis through `pos`
+#[derive(Debug)]
+pub struct Torus {
+    pub pos: Animated<Vec3>,
+    /// Distance from the centre to the middle of the tube
+    pub major: Animated<f64>,
+    /// Radius of the tube
+    pub minor: Animated<f64>,
+    pub(super) color: Animated<Color>,
+    pub reflectivity: Animated<f64>,
+}
+
+impl Torus {
+    /// Where a ray crosses the surface in order along it, and its unit direction
+    fn surfaces(&self, r: &Ray, t: f64) -> (Vec3, Vec<Surface>) {
+        let (pos, major, minor) = (self.pos.at(t), self.major.at(t), self.minor.at(t));
+        let u = (r.q - r.p).norm(); // Unit direction vector
+
+        // Solving from the point of the ray closest to the centre keeps the coefficients small
+        let start = -(r.p - pos).dot(u);
+        let o = r.p - pos + u * start;
+
+        // (|p|² + R² - r²)² = 4 R² (x² + z²) with p = o + k u
+        let (rr, f, g) = (major * major, o.dot(u), o.dot(o) + major * major - minor * minor);
+        let roots = roots::quartic(
+            4. * f,
+            4. * f * f + 2. * g - 4. * rr * (u.x * u.x + u.z * u.z),
+            4. * f * g - 8. * rr * (o.x * u.x + o.z * u.z),
+            g * g - 4. * rr * (o.x * o.x + o.z * o.z),
+        );
+
+        let surfaces = roots
+            .into_iter()
+            .map(|k| {
+                let k = k + start;
+                let intersection = r.p + u * k;
+                let p = intersection - pos;
+                // Away from the closest point in the middle of the tube
+                let around = (p.x * p.x + p.z * p.z).sqrt();
+                Surface {
+                    k,
+                    p: intersection,
+                    normal: (p - Vec3 { x: p.x, y: 0., z: p.z } * (major / around)).norm(),
+                    // Angle around the ring and around the tube
+                    uv: (0.5 + p.z.atan2(p.x) / (2. * PI), 0.5 + p.y.atan2(around - major) / (2. * PI)),
+                    color: self.color.at(t),
+                    reflectivity: self.reflectivity.at(t),
+                }
+            })
+            .collect();
+        (u, surfaces)
+    }
+}
+
+impl Renderable for Torus {
+    fn intersects(&self, r: &Ray, t: f64) -> Option<Hit> {
+        let (u, surfaces) = self.surfaces(r, t);
+        // Skips roots behind the start of the ray
+        surfaces.iter().find(|surface| surface.k >= 1.).map(|surface| surface.hit(u))
+    }
+
+    fn intervals(&self, r: &Ray, t: f64) -> Vec<Interval> {
+        // A ray enters and leaves the tube up to twice
+        self.surfaces(r, t)
+            .1
+            .chunks_exact(2)
+            .map(|pair| Interval {
+                enter: pair[0],
+                exit: pair[1],
+            })
+            .collect()
+    }
+}
+
+impl Fingerprint for Torus {
+    fn fingerprint(&self, h: &mut Fnv) {
+        let Torus { pos, major, minor, color, reflectivity } = self;
+        h.tag("torus");
+        pos.fingerprint(h);
+        major.fingerprint(h);
+        minor.fingerprint(h);
+        color.fingerprint(h);
+        reflectivity.fingerprint(h);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracer::Rgb;
+
+    fn ray(p: Vec3, dir: Vec3) -> Ray {
+        Ray { p, q: p + dir }
+    }
+
+    fn v(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).mag() < 1e-6, "{:?} != {:?}", a, b);
+    }
+
+    fn torus() -> Torus {
+        Torus {
+            pos: v(100., 0., 0.).into(),
+            major: Animated::Constant(20.),
+            minor: Animated::Constant(5.),
+            color: Color::from(Rgb::WHITE).into(),
+            reflectivity: Animated::Constant(0.),
+        }
+    }
+
+    #[test]
+    fn through_the_ring() {
+        let hit = torus().intersects(&ray(v(0., 0., 0.), v(1., 0., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(75., 0., 0.));
+        assert_close(hit.normal, v(-1., 0., 0.));
+    }
+
+    #[test]
+    fn down_onto_the_tube() {
+        let hit = torus().intersects(&ray(v(120., 50., 0.), v(0., -1., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(120., 5., 0.));
+        assert_close(hit.normal, v(0., 1., 0.));
+        // Straight down the hole
+        assert!(torus().intersects(&ray(v(100., 50., 0.), v(0., -1., 0.)), 0.).is_none());
+    }
+
+    #[test]
+    fn grazing() {
+        let graze = |y: f64| torus().intersects(&ray(v(0., y, 0.), v(1., 0., 0.)), 0.);
+        assert!(graze(5.001).is_none());
+        let hit = graze(4.999).unwrap();
+        assert!(hit.normal.y > 0.99);
+    }
+
+    #[test]
+    fn from_inside_the_tube() {
+        let hit = torus().intersects(&ray(v(120., 0., 0.), v(1., 0., 0.)), 0.).unwrap();
+        assert_close(hit.reflection.p, v(125., 0., 0.));
+        assert_close(hit.normal, v(-1., 0., 0.));
+    }
+
+    #[test]
+    fn miss() {
+        assert!(torus().intersects(&ray(v(0., 0., 50.), v(1., 0., 0.)), 0.).is_none());
+        assert!(torus().intersects(&ray(v(0., 0., 0.), v(-1., 0., 0.)), 0.).is_none());
+    }
+}
@@ -30,6 +30,25 @@ impl Vec3 {
         self.norm().dot(other.norm()).acos()
     }
 
+    /// Rotates around a unit axis, counterclockwise when looking against the axis
+    pub fn rotate(&self, axis: Vec3, angle: f64) -> Vec3 {
+        // Rodrigues' rotation formula
+        let (sin, cos) = angle.sin_cos();
+        *self * cos + axis.cross(*self) * sin + axis * (axis.dot(*self) * (1. - cos))
+    }
+
+    /// Two unit vectors perpendicular to this unit vector and to each other
+    pub fn tangents(&self) -> (Vec3, Vec3) {
+        // Cross with the axis the vector is least aligned with
+        let axis = if self.x.abs() < 0.9 {
+            Vec3 { x: 1., y: 0., z: 0. }
+        } else {
+            Vec3 { x: 0., y: 0., z: 1. }
+        };
+        let a = self.cross(axis).norm();
+        (a, self.cross(a))
+    }
+
     pub const NULL: Vec3 = Vec3 {
         x: 0.,
         y: 0.,
@@ -1,9 +1,227 @@
 use std::f64::consts::PI;
+use crate::raytracer::Camera;
 use crate::util::normalize;
 
 pub trait Renderer {
     /// Returns a texture as rgba pixel array with width and height
     fn render(&mut self, t: f64) -> (Vec<u8>, u16, u16);
+
+    /// Returns the image as rgb floats with width and height, 1.0 corresponds to 0xFF
+    ///
+    /// Renderers that compute colours in floating point return them unclamped.
+    fn render_hdr(&mut self, t: f64) -> (Vec<f32>, u16, u16) {
+        let (pixels, width, height) = self.render(t);
+        (to_rgb_f32(&pixels), width, height)
+    }
+
+    /// Like `render`, but slower and in higher quality if the renderer supports it
+    fn render_offline(&mut self, t: f64) -> (Vec<u8>, u16, u16) {
+        self.render(t)
+    }
+
+    /// Returns an auxiliary buffer of the last rendered frame as rgb floats, if the renderer records it
+    fn pass(&self, _pass: Pass) -> Option<Vec<f32>> {
+        None
+    }
+
+    /// Gives access to the camera for steering it interactively, if the renderer has one
+    fn camera(&mut self) -> Option<&mut Camera> {
+        None
+    }
+
+    /// Identifies everything that influences the rendered image, so stale frames can be detected
+    fn scene_hash(&self) -> u64 {
+        0
+    }
+
+    /// Counters of the last rendered frame
+    fn stats(&self) -> Stats {
+        Stats::default()
+    }
+}
+
+/// What it took to render a frame
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    /// Camera rays and reflections traced
+    pub rays: u64,
+    /// Samples per pixel
+    pub samples: u32,
+}
+
+/// Converts rgba bytes to rgb floats
+pub fn to_rgb_f32(pixels: &[u8]) -> Vec<f32> {
+    pixels
+        .chunks(4)
+        .flat_map(|p| p[..3].iter().map(|&c| c as f32 / 255.))
+        .collect()
+}
+
+/// Converts rgb floats to opaque rgba bytes, clamping values outside 0..1
+pub fn to_rgba8(pixels: &[f32]) -> Vec<u8> {
+    let c = |v: f32| (v.clamp(0., 1.) * 255.).round() as u8;
+    let mut bytes = Vec::with_capacity(pixels.len() / 3 * 4);
+    for p in pixels.chunks(3) {
+        bytes.extend_from_slice(&[c(p[0]), c(p[1]), c(p[2]), 0xFF]);
+    }
+    bytes
+}
+
+/// A buffer of a rendered frame, the beauty pass is the image itself
+///
+/// Scalar passes repeat their value in all three channels. Pixels where the
+/// camera ray hit nothing are 0, so object ids start at 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Pass {
+    Beauty,
+    /// Distance from the camera along the view direction
+    Depth,
+    /// World space normal of the surface
+    Normal,
+    /// Surface colour without reflections
+    Albedo,
+    ObjectId,
+    /// Number of reflections followed
+    Bounces,
+    /// World space position of the surface
+    Position,
+    /// Number of ray-object intersection tests
+    Tests,
+    /// Texture coordinates of the surface, repeating from 0 to 1
+    Uv,
+}
+
+impl Pass {
+    pub const AUXILIARY: [Pass; 8] = [
+        Pass::Depth,
+        Pass::Normal,
+        Pass::Albedo,
+        Pass::ObjectId,
+        Pass::Bounces,
+        Pass::Position,
+        Pass::Tests,
+        Pass::Uv,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pass::Beauty => "beauty",
+            Pass::Depth => "depth",
+            Pass::Normal => "normal",
+            Pass::Albedo => "albedo",
+            Pass::ObjectId => "object",
+            Pass::Bounces => "bounces",
+            Pass::Position => "position",
+            Pass::Tests => "tests",
+            Pass::Uv => "uv",
+        }
+    }
+
+    /// The pass after this one, wrapping around to the beauty pass
+    pub fn next(&self) -> Pass {
+        match Pass::AUXILIARY.iter().position(|p| p == self) {
+            None => Pass::AUXILIARY[0],
+            Some(i) if i + 1 < Pass::AUXILIARY.len() => Pass::AUXILIARY[i + 1],
+            Some(_) => Pass::Beauty,
+        }
+    }
+
+    /// Maps the values of this pass into 0..1 for viewing
+    pub fn normalize(&self, pixels: &[f32]) -> Vec<f32> {
+        let max = pixels.iter().fold(0f32, |max, v| max.max(v.abs()));
+        let scale = if max > 0. { 1. / max } else { 0. };
+        match self {
+            Pass::Beauty | Pass::Albedo | Pass::Uv => pixels.to_vec(),
+            Pass::Normal => pixels.iter().map(|v| v * 0.5 + 0.5).collect(),
+            Pass::Position => pixels.iter().map(|v| v * scale * 0.5 + 0.5).collect(),
+            Pass::Depth | Pass::Bounces | Pass::Tests => pixels.iter().map(|v| v * scale).collect(),
+            Pass::ObjectId => pixels
+                .chunks(3)
+                .flat_map(|p| {
+                    // Spread neighbouring ids over the hue circle
+                    let id = p[0] as u32;
+                    let hue = (id as f32 * 0.618034).fract();
+                    let brightness = if id == 0 { 0. } else { 1. };
+                    (0..3).map(move |c| {
+                        let phase = hue + c as f32 / 3.;
+                        brightness * (0.5 + 0.5 * (phase * 2. * std::f32::consts::PI).cos())
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Pass {
+    /// Debug visualisation for the viewer, counts and depth are shown as heatmaps
+    pub fn show(&self, pixels: &[f32]) -> Vec<u8> {
+        match self {
+            Pass::Depth | Pass::Bounces | Pass::Tests => {
+                // Depth starts where the closest surface is, 0 stays black for the background
+                let hit = || pixels.iter().step_by(3).filter(|v| **v > 0.);
+                let max = hit().fold(0f32, |max, v| max.max(*v));
+                let min = match self {
+                    Pass::Depth => hit().fold(max, |min, v| min.min(*v)),
+                    _ => 0.,
+                };
+                let range = (max - min).max(f32::EPSILON);
+                let mut colors = Vec::with_capacity(pixels.len());
+                for p in pixels.chunks(3) {
+                    if p[0] > 0. {
+                        colors.extend_from_slice(&heatmap((p[0] - min) / range));
+                    } else {
+                        colors.extend_from_slice(&[0., 0., 0.]);
+                    }
+                }
+                to_rgba8(&colors)
+            }
+            _ => to_rgba8(&self.normalize(pixels)),
+        }
+    }
+}
+
+/// Turbo colour map from blue over green to red, polynomial approximation by Google
+fn heatmap(x: f32) -> [f32; 3] {
+    let x = x.clamp(0., 1.);
+    [
+        0.13572138 + x * (4.6153926 + x * (-42.660323 + x * (132.13108 + x * (-152.9424 + x * 59.28638)))),
+        0.09140261 + x * (2.1941884 + x * (4.8429666 + x * (-14.185033 + x * (4.2772985 + x * 2.829566)))),
+        0.1066733 + x * (12.641946 + x * (-60.582047 + x * (110.36277 + x * (-89.90311 + x * 27.34825)))),
+    ]
+}
+
+pub const FPS: f64 = 30.;
+
+/// Evenly spaced frame times from start to end, both inclusive
+#[derive(Clone, Copy, Debug)]
+pub struct FrameRange {
+    pub start: f64,
+    pub steps: u64,
+    pub stepsize: f64,
+    pub fps: f64,
+}
+
+impl FrameRange {
+    pub fn new(start: f64, end: f64, fps: f64) -> FrameRange {
+        let interval = end - start;
+        let steps = (interval * fps).round() as u64;
+        // A range shorter than half a frame is a single frame at the start
+        let stepsize = if steps == 0 { 0. } else { interval / (steps as f64) };
+        FrameRange {
+            start,
+            steps,
+            stepsize,
+            fps,
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.steps + 1
+    }
+
+    pub fn time(&self, frame: u64) -> f64 {
+        frame as f64 * self.stepsize + self.start
+    }
 }
 
 const TEXTURE_WIDTH: u16 = 800;
@@ -72,4 +290,23 @@ impl Renderer for LightUpRenderer {
         }
         (pixels, TEXTURE_WIDTH, TEXTURE_HEIGHT)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_range() {
+        let frames = FrameRange::new(1., 3., FPS);
+        assert_eq!(frames.count(), 61);
+        assert_eq!(frames.time(0), 1.);
+        assert!((frames.time(60) - 3.).abs() < 1e-9);
+        // Too short for a second frame
+        for end in [1., 1.01] {
+            let frames = FrameRange::new(1., end, FPS);
+            assert_eq!(frames.count(), 1);
+            assert_eq!(frames.time(0), 1.);
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::io::{ErrorKind, Write};
+
+use crate::raytracer::Camera;
+use crate::renderer::{FrameRange, Pass, Renderer, Stats};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StreamFormat {
+    /// YUV4MPEG2 with 4:2:0 chroma subsampling, understood by ffmpeg & co.
+    Y4m,
+    /// Plain rgba bytes without any header
+    Rgba,
+}
+
+/// Passes frames through while streaming them as uncompressed video
+pub struct StreamRenderer<T> where T: Renderer {
+    renderer: T,
+    out: Box<dyn Write>,
+    format: StreamFormat,
+    fps: f64,
+    header_written: bool,
+    /// The reader went away, like `head` or an ffmpeg that has enough
+    closed: bool,
+}
+
+impl<T> StreamRenderer<T> where T: Renderer {
+    pub fn new(renderer: T, out: Box<dyn Write>, format: StreamFormat, fps: f64) -> StreamRenderer<T> {
+        StreamRenderer {
+            renderer, out, format, fps, header_written: false, closed: false,
+        }
+    }
+
+    /// Streams all frames of the range without a window
+    pub fn render_all(&mut self, frames: FrameRange) {
+        for frame in 0..frames.count() {
+            if self.closed {
+                break;
+            }
+            self.render(frames.time(frame));
+            info!("Frame {} of {} done", frame, frames.steps);
+        }
+        let result = self.out.flush();
+        self.check(result);
+    }
+
+    /// Writes to the stream until the reader goes away
+    fn write(&mut self, bytes: &[u8]) {
+        if !self.closed {
+            let result = self.out.write_all(bytes);
+            self.check(result);
+        }
+    }
+
+    /// A closed pipe ends the stream, other errors are fatal
+    fn check(&mut self, result: std::io::Result<()>) {
+        match result {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => {
+                if !self.closed {
+                    info!("Stream closed by the reader");
+                }
+                self.closed = true;
+            }
+            Err(e) => panic!("Can't write the stream: {}", e),
+        }
+    }
+
+    fn write_y4m(&mut self, pixels: &[u8], width: u16, height: u16) {
+        if !self.header_written {
+            let (num, den) = if self.fps.fract() == 0. {
+                (self.fps as u64, 1)
+            } else {
+                ((self.fps * 1000.).round() as u64, 1000)
+            };
+            // Progressive frames with square pixels
+            let header = format!("YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C420jpeg\n", width, height, num, den);
+            self.write(header.as_bytes());
+            self.header_written = true;
+        }
+        let (y, u, v) = yuv420(pixels, width as usize, height as usize);
+        self.write(b"FRAME\n");
+        self.write(&y);
+        self.write(&u);
+        self.write(&v);
+    }
+}
+
+/// Converts rgba to BT.601 studio range Y, U and V planes, U and V at half resolution
+fn yuv420(pixels: &[u8], width: usize, height: usize) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+    let rgb = |x: usize, y: usize| {
+        let i = (y * width + x) * 4;
+        (pixels[i] as f64 / 255., pixels[i + 1] as f64 / 255., pixels[i + 2] as f64 / 255.)
+    };
+
+    let mut luma = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let (r, g, b) = rgb(x, y);
+            luma.push((16. + 219. * (0.299 * r + 0.587 * g + 0.114 * b)).round() as u8);
+        }
+    }
+
+    let chroma_width = width.div_ceil(2);
+    let chroma_height = height.div_ceil(2);
+    let mut u = Vec::with_capacity(chroma_width * chroma_height);
+    let mut v = Vec::with_capacity(chroma_width * chroma_height);
+    for cy in 0..chroma_height {
+        for cx in 0..chroma_width {
+            // Average the (up to) four pixels covered by this chroma sample
+            let (mut r, mut g, mut b, mut n) = (0., 0., 0., 0.);
+            for y in (2 * cy)..(2 * cy + 2).min(height) {
+                for x in (2 * cx)..(2 * cx + 2).min(width) {
+                    let p = rgb(x, y);
+                    r += p.0;
+                    g += p.1;
+                    b += p.2;
+                    n += 1.;
+                }
+            }
+            let (r, g, b) = (r / n, g / n, b / n);
+            u.push((128. + 224. * (-0.168736 * r - 0.331264 * g + 0.5 * b)).round() as u8);
+            v.push((128. + 224. * (0.5 * r - 0.418688 * g - 0.081312 * b)).round() as u8);
+        }
+    }
+    (luma, u, v)
+}
+
+impl<T> Renderer for StreamRenderer<T> where T: Renderer {
+    fn render(&mut self, t: f64) -> (Vec<u8>, u16, u16) {
+        let r = self.renderer.render(t);
+        match self.format {
+            StreamFormat::Y4m => self.write_y4m(&r.0, r.1, r.2),
+            StreamFormat::Rgba => self.write(&r.0),
+        }
+        r
+    }
+
+    fn render_offline(&mut self, t: f64) -> (Vec<u8>, u16, u16) {
+        self.renderer.render_offline(t)
+    }
+
+    fn pass(&self, pass: Pass) -> Option<Vec<f32>> {
+        self.renderer.pass(pass)
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        self.renderer.camera()
+    }
+
+    fn scene_hash(&self) -> u64 {
+        self.renderer.scene_hash()
+    }
+
+    fn stats(&self) -> Stats {
+        self.renderer.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grey_has_no_colour() {
+        // 130 is as close as 8 bits get to the middle of the studio range
+        let (y, u, v) = yuv420(&[130; 4 * 4 * 2], 4, 2);
+        assert_eq!(y, [128; 8]);
+        assert_eq!(u, [128; 2]);
+        assert_eq!(v, [128; 2]);
+    }
+
+    #[test]
+    fn odd_sizes_round_up() {
+        let mut pixels = vec![0; 3 * 3 * 4];
+        // Red in the bottom right corner, which has a chroma sample of its own
+        pixels[8 * 4] = 255;
+        let (y, u, v) = yuv420(&pixels, 3, 3);
+        assert_eq!((y.len(), u.len(), v.len()), (9, 4, 4));
+        assert_eq!(y[..8], [16; 8]);
+        assert_eq!(v[..3], [128; 3]);
+        assert_eq!(v[3], 240);
+    }
+
+    /// Frames of one black pixel
+    struct Black(usize);
+
+    impl Renderer for Black {
+        fn render(&mut self, _t: f64) -> (Vec<u8>, u16, u16) {
+            self.0 += 1;
+            (vec![0; 4], 1, 1)
+        }
+    }
+
+    /// A pipe whose reader has gone away
+    struct Closed;
+
+    impl Write for Closed {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Err(ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[test]
+    fn closed_pipe_ends_the_stream() {
+        let mut stream = StreamRenderer::new(Black(0), Box::new(Closed), StreamFormat::Y4m, 30.);
+        stream.render_all(FrameRange::new(0., 1., 30.));
+        assert_eq!(stream.renderer.0, 1);
+    }
+}
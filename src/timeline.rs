@@ -0,0 +1,193 @@
+use miniquad::KeyCode;
+
+use crate::renderer::FPS;
+
+/// Animation time of the viewer, advanced by the wall clock unless paused
+pub struct Timeline {
+    pub t: f64,
+    pub paused: bool,
+    /// Animation seconds per wall clock second
+    pub speed: f64,
+    /// Start and end the animation wraps around in
+    pub range: Option<(f64, f64)>,
+    /// Where the animation starts, `Home` jumps here without a loop
+    start: f64,
+    /// Wall clock time of the last advance
+    last: f64,
+    /// Digits typed to jump to a time
+    typed: String,
+}
+
+impl Timeline {
+    pub fn new(t: f64, start: f64, now: f64) -> Timeline {
+        Timeline {
+            t,
+            paused: false,
+            speed: 1.,
+            range: None,
+            start,
+            last: now,
+            typed: String::new(),
+        }
+    }
+
+    /// Moves on by the wall clock time since the last call and returns the animation time
+    pub fn advance(&mut self, now: f64) -> f64 {
+        if !self.paused {
+            self.t += (now - self.last) * self.speed;
+            self.wrap();
+        }
+        self.last = now;
+        self.t
+    }
+
+    /// Pauses and moves by a number of frames, backwards if negative
+    pub fn step(&mut self, frames: i32) {
+        self.paused = true;
+        self.t += frames as f64 / FPS;
+        self.wrap();
+    }
+
+    pub fn jump(&mut self, t: f64) {
+        self.t = t;
+        self.wrap();
+    }
+
+    fn wrap(&mut self) {
+        if let Some((start, end)) = self.range {
+            if end > start && (self.t < start || self.t >= end) {
+                self.t = start + (self.t - start).rem_euclid(end - start);
+            }
+        }
+    }
+
+    /// Handles the timeline keys, returns false for other keys
+    ///
+    /// `Space` pauses, `Left` and `Right` step a frame, `-` and `=` change the
+    /// speed, `[` and `]` set the loop start and end, `\` removes the loop,
+    /// `Home` jumps to the start of the loop or the animation and typing a number followed by `Enter` jumps
+    /// to that time.
+    pub fn key_down(&mut self, keycode: KeyCode) -> bool {
+        let digit = match keycode {
+            KeyCode::Key0 => Some('0'),
+            KeyCode::Key1 => Some('1'),
+            KeyCode::Key2 => Some('2'),
+            KeyCode::Key3 => Some('3'),
+            KeyCode::Key4 => Some('4'),
+            KeyCode::Key5 => Some('5'),
+            KeyCode::Key6 => Some('6'),
+            KeyCode::Key7 => Some('7'),
+            KeyCode::Key8 => Some('8'),
+            KeyCode::Key9 => Some('9'),
+            KeyCode::Period => Some('.'),
+            _ => None,
+        };
+        if let Some(digit) = digit {
+            self.typed.push(digit);
+            info!("Jump to: {}", self.typed);
+            return true;
+        }
+
+        match keycode {
+            KeyCode::Space => self.paused = !self.paused,
+            KeyCode::Right => self.step(1),
+            KeyCode::Left => self.step(-1),
+            KeyCode::Equal => self.speed *= 2.,
+            KeyCode::Minus => self.speed /= 2.,
+            KeyCode::LeftBracket => self.range = Some((self.t, self.range.map_or(self.t, |r| r.1).max(self.t))),
+            KeyCode::RightBracket => self.range = Some((self.range.map_or(self.start, |r| r.0).min(self.t), self.t)),
+            KeyCode::Backslash => self.range = None,
+            KeyCode::Home => self.jump(self.range.map_or(self.start, |r| r.0)),
+            KeyCode::Backspace => {
+                self.typed.pop();
+            }
+            KeyCode::Enter => {
+                if let Ok(t) = self.typed.parse() {
+                    self.jump(t);
+                }
+                self.typed.clear();
+            }
+            _ => return false,
+        }
+        info!(
+            "t: {:.3} speed: {}x paused: {} loop: {:?}",
+            self.t, self.speed, self.paused, self.range
+        );
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn advances_by_speed() {
+        let mut timeline = Timeline::new(1., 0., 10.);
+        assert!(close(timeline.advance(10.5), 1.5));
+        timeline.key_down(KeyCode::Equal);
+        assert!(close(timeline.advance(11.), 2.5));
+        timeline.key_down(KeyCode::Space);
+        assert!(close(timeline.advance(20.), 2.5));
+    }
+
+    #[test]
+    fn wraps_inside_the_loop() {
+        let mut timeline = Timeline::new(1., 0., 0.);
+        timeline.range = Some((1., 3.));
+        assert!(close(timeline.advance(2.5), 1.5));
+        assert!(close(timeline.advance(7.5), 2.5));
+        // The end is the start again
+        assert!(close(timeline.advance(8.), 1.));
+        timeline.jump(0.5);
+        assert!(close(timeline.t, 2.5));
+    }
+
+    #[test]
+    fn steps_whole_frames() {
+        let mut timeline = Timeline::new(1., 0., 0.);
+        timeline.key_down(KeyCode::Right);
+        assert!(timeline.paused);
+        assert!(close(timeline.t, 1. + 1. / FPS));
+        timeline.step(-2);
+        assert!(close(timeline.t, 1. - 1. / FPS));
+
+        // Stepping back from the start of a loop goes to its last frame
+        timeline.range = Some((1., 2.));
+        timeline.jump(1.);
+        timeline.step(-1);
+        assert!(close(timeline.t, 2. - 1. / FPS));
+        timeline.step(1);
+        assert!(close(timeline.t, 1.));
+    }
+
+    #[test]
+    fn loop_keys() {
+        let mut timeline = Timeline::new(2., 0.5, 0.);
+        timeline.key_down(KeyCode::RightBracket);
+        assert_eq!(timeline.range, Some((0.5, 2.)));
+        timeline.jump(1.);
+        timeline.key_down(KeyCode::LeftBracket);
+        assert_eq!(timeline.range, Some((1., 2.)));
+        timeline.key_down(KeyCode::Home);
+        assert_eq!(timeline.t, 1.);
+        timeline.key_down(KeyCode::Backslash);
+        timeline.key_down(KeyCode::Home);
+        assert_eq!(timeline.t, 0.5);
+    }
+
+    #[test]
+    fn typed_jumps() {
+        let mut timeline = Timeline::new(0., 0., 0.);
+        for key in [KeyCode::Key1, KeyCode::Period, KeyCode::Key5, KeyCode::Key9, KeyCode::Backspace] {
+            assert!(timeline.key_down(key));
+        }
+        timeline.key_down(KeyCode::Enter);
+        assert_eq!(timeline.t, 1.5);
+        assert!(!timeline.key_down(KeyCode::X));
+    }
+}